@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use poke_fighting_rust::{Battle, Pokemon, SelectionAlgorithm, UpdateMode};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+fn action(c: &mut Criterion) {
+    let mut group = c.benchmark_group("action");
+    group.sample_size(10);
+
+    for size in [256, 1024, 2048] {
+        group.throughput(Throughput::Elements((size * size) as u64));
+        for update_mode in [UpdateMode::Sequential, UpdateMode::Parallel] {
+            let rng = ChaCha8Rng::seed_from_u64(1);
+            let mut battle =
+                Battle::<Pokemon>::new(size, size, SelectionAlgorithm::WeakestNeighbour, true, rng);
+            battle.set_update_mode(update_mode);
+
+            let id = BenchmarkId::new(format!("{update_mode:?}"), size);
+            group.bench_function(id, |b| b.iter(|| battle.action()));
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, action);
+criterion_main!(benches);
@@ -1,10 +1,23 @@
+use crate::battle::{is_probability, Battle, Fighter, SelectionAlgorithm, UpdateMode};
+use crate::grid::Boundary;
+use crate::layout::{load_terrain, ImageLayout, Layout, LayoutKind};
+use crate::neighbourhood::{Neighbourhood, NeighbourhoodKind};
+use crate::render::RenderMode;
+use crate::snapshot::{load_snapshot, snapshot_fighter_type};
+use crate::types::{Colored, FighterOptions, GenerateRandomly, Typed};
 use clap::{Parser, ValueEnum};
-use serde::Deserialize;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 
 /// Battle simulation
-#[derive(Debug, Deserialize, Parser)]
+#[derive(Clone, Debug, Deserialize, Parser)]
 pub struct Args {
-    /// Fighter type, either pokemon or rps
+    /// Fighter type, custom reads the fighter table from --table
     #[clap(value_enum, short='t', long, default_value_t = default_fighter_type())]
     #[serde(default = "default_fighter_type")]
     pub fighter_type: FighterType,
@@ -24,15 +37,286 @@ pub struct Args {
     #[serde(default)]
     pub random: bool,
 
+    /// Cells a fighter can pick its opponent from
+    #[clap(value_enum, long, default_value_t = NeighbourhoodKind::default())]
+    #[serde(default)]
+    pub neighbourhood: NeighbourhoodKind,
+
+    /// Radius of the neighbourhood
+    #[clap(long, default_value_t = default_one(), value_parser = validate_positive)]
+    #[serde(default = "default_one")]
+    pub radius: usize,
+
+    /// What happens at the edges of the grid
+    #[clap(value_enum, long, default_value_t = Boundary::default())]
+    #[serde(default)]
+    pub boundary: Boundary,
+
+    /// How the fights of a round are scheduled, parallel uses all cores
+    #[clap(value_enum, long, default_value_t = UpdateMode::default())]
+    #[serde(default)]
+    pub update_mode: UpdateMode,
+
+    /// Pattern the fighters of a new grid start in
+    #[clap(value_enum, long, default_value_t = LayoutKind::default())]
+    #[serde(default)]
+    pub layout: LayoutKind,
+
+    /// Width of the stripes, rings and squares of the layout and the average size of its blobs
+    #[clap(long, default_value_t = default_layout_scale(), value_parser = validate_positive)]
+    #[serde(default = "default_layout_scale")]
+    pub layout_scale: usize,
+
+    /// Comma separated share of every type in a new grid, in the order of the types
+    #[clap(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub weights: Vec<u32>,
+
+    /// Image the grid starts from instead of the layout, every pixel becomes a fighter of the
+    /// type with the closest color and the grid takes the size of the image
+    #[clap(long)]
+    #[serde(default)]
+    pub image: Option<PathBuf>,
+
+    /// Black and white image of walls that never fight, scaled to the size of the grid
+    #[clap(long)]
+    #[serde(default)]
+    pub terrain: Option<PathBuf>,
+
+    /// Chance of a fighter to reproduce into an empty neighbouring cell every round. When passed,
+    /// defeated fighters leave their cell empty instead of changing their type
+    #[clap(long, value_parser = validate_probability)]
+    #[serde(default)]
+    pub reproduction: Option<f64>,
+
+    /// Share of the full health every fighter heals at the end of a round
+    #[clap(long, value_parser = validate_probability)]
+    #[serde(default)]
+    pub regeneration: Option<f64>,
+
+    /// Let fighters gain experience by winning fights, higher levels do more damage and have more
+    /// health and converted fighters take over half the experience of their winner
+    #[clap(long)]
+    #[serde(default)]
+    pub leveling: bool,
+
+    /// Give half of the Pokémon a second type, which changes how effective attacks against them are
+    #[clap(long)]
+    #[serde(default)]
+    pub dual_types: bool,
+
+    /// Give every Pokémon type its own health, attack, defense and speed. Pokémon pick their best
+    /// move, moves of their own type do more damage and faster Pokémon strike first.
+    #[clap(long)]
+    #[serde(default)]
+    pub base_stats: bool,
+
     /// Measure frame rate and print it to stdout
     #[clap(short = 'f', long)]
     #[serde(default)]
     pub framerate: bool,
 
+    /// What the color of a fighter shows
+    #[clap(value_enum, long, default_value_t = RenderMode::default())]
+    #[serde(default)]
+    pub render_mode: RenderMode,
+
     /// Let fighters fight their own kind
     #[clap(short = 'o', long)]
     #[serde(default)]
     pub fightown: bool,
+
+    /// Seed for the random number generator, runs with the same seed are reproducible
+    #[clap(short = 's', long)]
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// Fighter table JSON file used by the custom fighter type
+    #[clap(long)]
+    #[serde(default)]
+    pub table: Option<PathBuf>,
+
+    /// Rounds simulated per frame in the window
+    #[clap(long, default_value_t = default_one(), value_parser = validate_positive)]
+    #[serde(default = "default_one")]
+    pub rounds_per_frame: usize,
+
+    /// What happens when only one type is left or the battle is a stalemate, it is always reported
+    #[clap(value_enum, long, default_value_t = EndAction::default())]
+    #[serde(default)]
+    pub on_end: EndAction,
+
+    /// Rounds without a single conversion after which the battle is a stalemate
+    #[clap(long, default_value_t = default_stalemate_rounds(), value_parser = validate_positive)]
+    #[serde(default = "default_stalemate_rounds")]
+    pub stalemate_rounds: usize,
+
+    /// Number of rounds to simulate in headless mode
+    #[clap(short = 'n', long, default_value_t = default_rounds())]
+    #[serde(default = "default_rounds")]
+    pub rounds: usize,
+
+    /// Image file the final grid is written to in headless mode
+    #[clap(long, default_value_os_t = default_output())]
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+
+    /// File the per-round statistics are written to on exit, as JSON for .json files and CSV
+    /// otherwise
+    #[clap(long)]
+    #[serde(default)]
+    pub stats: Option<PathBuf>,
+
+    /// Directory recorded frames are written to as numbered PNG files
+    #[clap(long)]
+    #[serde(default)]
+    pub frames: Option<PathBuf>,
+
+    /// Animated GIF file recorded frames are encoded into
+    #[clap(long)]
+    #[serde(default)]
+    pub gif: Option<PathBuf>,
+
+    /// Record a frame every N rounds
+    #[clap(long, default_value_t = default_one(), value_parser = validate_positive)]
+    #[serde(default = "default_one")]
+    pub frame_interval: usize,
+
+    /// Delay between GIF frames in milliseconds
+    #[clap(long, default_value_t = default_frame_delay())]
+    #[serde(default = "default_frame_delay")]
+    pub frame_delay: u16,
+
+    /// Scale factor applied to recorded frames
+    #[clap(long, default_value_t = default_one(), value_parser = validate_positive)]
+    #[serde(default = "default_one")]
+    pub frame_scale: usize,
+
+    /// Snapshot file to continue a previous run from, its fighter type and size take precedence
+    #[clap(long)]
+    #[serde(default)]
+    pub load_snapshot: Option<PathBuf>,
+
+    /// Snapshot file written when pressing S, or at the end of a headless run
+    #[clap(long)]
+    #[serde(default)]
+    pub snapshot: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn selection_algorithm(&self) -> SelectionAlgorithm {
+        if self.random {
+            SelectionAlgorithm::RandomNeighbour
+        } else {
+            SelectionAlgorithm::WeakestNeighbour
+        }
+    }
+
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        Neighbourhood::new(self.neighbourhood, self.radius)
+    }
+
+    pub fn layout(&self) -> Layout {
+        Layout {
+            kind: self.layout,
+            scale: self.layout_scale,
+            weights: self.weights.clone(),
+        }
+    }
+
+    /// Checks the settings clap only checks on the command line, for a config or sweep file
+    fn validate(&self) -> io::Result<()> {
+        let probabilities = [
+            ("reproduction", self.reproduction),
+            ("regeneration", self.regeneration),
+        ];
+        for (name, value) in probabilities {
+            if value.is_some_and(|value| !is_probability(value)) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{name} should be between 0 and 1"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fighter_options(&self) -> FighterOptions {
+        FighterOptions {
+            dual_types: self.dual_types,
+            base_stats: self.base_stats,
+        }
+    }
+
+    pub fn rng(&self) -> ChaCha8Rng {
+        match self.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        }
+    }
+
+    /// The fighter type of the snapshot being loaded, or the configured one otherwise
+    pub fn resolve_fighter_type(&self) -> io::Result<FighterType> {
+        match &self.load_snapshot {
+            Some(path) => snapshot_fighter_type(path),
+            None => Ok(self.fighter_type.clone()),
+        }
+    }
+
+    pub fn create_battle<T>(&self, fighter_type: &FighterType) -> io::Result<Battle<T>>
+    where
+        T: Colored + GenerateRandomly + Fighter + Typed + DeserializeOwned,
+    {
+        match &self.load_snapshot {
+            Some(path) => load_snapshot(path, fighter_type),
+            None => {
+                self.validate()?;
+                let layout = self.layout();
+                layout.validate(T::type_names().len())?;
+                if layout.kind == LayoutKind::Seeds && self.reproduction.is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the seeds layout needs --reproduction to spread",
+                    ));
+                }
+                let mut battle = match &self.image {
+                    Some(path) => {
+                        let image = ImageLayout::open(path)?;
+                        let (width, height) = image.size();
+                        Battle::with_initializer(
+                            width,
+                            height,
+                            self.selection_algorithm(),
+                            !self.fightown,
+                            self.rng(),
+                            &image,
+                        )
+                    }
+                    None => Battle::with_initializer(
+                        self.width,
+                        self.height,
+                        self.selection_algorithm(),
+                        !self.fightown,
+                        self.rng(),
+                        &layout,
+                    ),
+                };
+                if let Some(path) = &self.terrain {
+                    battle.set_terrain(load_terrain(path, battle.size())?);
+                }
+                battle.set_neighbourhood(self.neighbourhood());
+                battle.set_reproduction(self.reproduction);
+                battle.set_regeneration(self.regeneration);
+                battle.set_leveling(self.leveling);
+                battle.set_options(self.fighter_options());
+                battle.set_boundary(self.boundary);
+                battle.set_update_mode(self.update_mode);
+                // A restart uses the layout, even when the grid started from an image
+                battle.set_layout(layout);
+                Ok(battle)
+            }
+        }
+    }
 }
 
 fn default_fighter_type() -> FighterType {
@@ -43,20 +327,79 @@ fn default_size() -> usize {
     512
 }
 
-#[derive(ValueEnum, Clone, Debug, Deserialize)]
-#[serde(rename_all(deserialize = "kebab-case"))]
+fn default_rounds() -> usize {
+    1000
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("battle.png")
+}
+
+fn default_one() -> usize {
+    1
+}
+
+fn default_frame_delay() -> u16 {
+    50
+}
+
+fn default_layout_scale() -> usize {
+    16
+}
+
+fn default_stalemate_rounds() -> usize {
+    100
+}
+
+#[derive(ValueEnum, Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
 pub enum FighterType {
     Pokemon,
     RockPaperScissors,
     StreetFighter,
     ColorFighter,
+    Custom,
+}
+
+/// What to do once a battle has ended
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndAction {
+    /// Only report it and keep going
+    #[default]
+    Continue,
+    /// Pause the window, headless runs stop early
+    Pause,
+    /// Print the share of every type and exit
+    Exit,
+    /// Start again with a new grid in the same layout
+    Restart,
+}
+
+/// Sizes a side of the grid can have, wgpu won't allow more than 8192 pixels
+pub const GRID_SIZES: RangeInclusive<usize> = 32..=8192;
+
+/// Checks that both sides of a grid are within [`GRID_SIZES`]
+pub(crate) fn validate_dimensions(width: usize, height: usize) -> Result<(), String> {
+    if GRID_SIZES.contains(&width) && GRID_SIZES.contains(&height) {
+        Ok(())
+    } else {
+        Err(format!(
+            "size {width}x{height} should be between {} and {}",
+            GRID_SIZES.start(),
+            GRID_SIZES.end()
+        ))
+    }
 }
 
 fn validate_size(arg: &str) -> Result<usize, String> {
     if let Ok(size) = arg.parse::<usize>() {
-        // wgpu won't allow more than 8192 pixels
-        if !(32..8193).contains(&size) {
-            return Err("image size should be between 32 and 8192".to_string());
+        if !GRID_SIZES.contains(&size) {
+            return Err(format!(
+                "image size should be between {} and {}",
+                GRID_SIZES.start(),
+                GRID_SIZES.end()
+            ));
         } else {
             return Ok(size);
         }
@@ -64,3 +407,19 @@ fn validate_size(arg: &str) -> Result<usize, String> {
 
     Err("Size is not a number".to_string())
 }
+
+fn validate_positive(arg: &str) -> Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(0) => Err("value should be at least 1".to_string()),
+        Ok(value) => Ok(value),
+        Err(_) => Err("value is not a number".to_string()),
+    }
+}
+
+fn validate_probability(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if is_probability(value) => Ok(value),
+        Ok(_) => Err("value should be between 0 and 1".to_string()),
+        Err(_) => Err("value is not a number".to_string()),
+    }
+}
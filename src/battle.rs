@@ -1,84 +1,670 @@
-use crate::grid::{Grid2D, Size};
-use crate::types::GenerateRandomly;
+use crate::grid::{Boundary, Grid2D, GridView, Size};
+use crate::layout::Layout;
+use crate::neighbourhood::Neighbourhood;
+use crate::stats::{Outcome, RoundStats, Statistics};
+use crate::types::{FighterOptions, GenerateRandomly, Initializer, Typed};
+use clap::ValueEnum;
 use rand::seq::IteratorRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::ops::Range;
 
 type Location = (usize, usize);
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub enum SelectionAlgorithm {
     WeakestNeighbour,
     RandomNeighbour,
 }
 
+/// How `Battle::action` schedules the fights of a round
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateMode {
+    /// One fighter after the other, in a semi-random order
+    #[default]
+    Sequential,
+    /// Bands of the grid on all cores, deterministic for a given seed but not identical to sequential
+    Parallel,
+    /// All fighters attack at once based on the previous round, like a cellular automaton
+    Synchronous,
+}
+
 pub trait Fighter {
     fn should_fight(&self, defender: &Self) -> bool;
     fn get_effectiveness(&self, defender: &Self) -> i32;
-    fn fight(&self, defender: &mut Self) -> bool;
+    fn fight<R>(&self, defender: &mut Self, rng: &mut R) -> bool
+    where
+        R: Rng;
+
+    /// Whether a defender strikes before the attacker that picked it, by default nobody is faster
+    fn strikes_first(&self, _attacker: &Self) -> bool {
+        false
+    }
+
+    /// Whether a defender that survived the attack strikes back, by default only attackers strike
+    fn strikes_back(&self, _attacker: &Self) -> bool {
+        false
+    }
+
+    /// Applies the options of the battle, fighters without options ignore them
+    fn configure<R>(&mut self, _options: &FighterOptions, _rng: &mut R)
+    where
+        R: Rng,
+    {
+    }
+
+    /// Remaining share of the full health, between 0 and 1
+    fn health(&self) -> f32 {
+        1.0
+    }
+
+    /// Heals `rate` of the full health, fighters without health ignore it
+    fn regenerate(&mut self, _rate: f64) {}
+
+    /// Level reached by winning fights, fighters that never improve stay at level 1
+    fn level(&self) -> u32 {
+        1
+    }
+
+    /// Called on the winner of a fight when leveling is on
+    fn gain_experience(&mut self) {}
+
+    /// Called on a fighter right after `winner` converted it when leveling is on
+    fn inherit_experience(&mut self, _winner: &Self) {}
 }
 
-pub struct Battle<T> {
+#[derive(Deserialize, Serialize)]
+pub struct Battle<T, R = ChaCha8Rng> {
     fighters: Grid2D<T>,
-    rng: rand::rngs::ThreadRng,
-    selection_callback: fn(&mut Self, Location, Size) -> Option<Location>,
+    rng: R,
+    selection_algorithm: SelectionAlgorithm,
+    filter_fight_candidates: bool,
+    #[serde(default)]
+    neighbourhood: Neighbourhood,
+    #[serde(default)]
+    update_mode: UpdateMode,
+    #[serde(default)]
+    reproduction: Option<f64>,
+    #[serde(default)]
+    regeneration: Option<f64>,
+    #[serde(default)]
+    leveling: bool,
+    #[serde(default)]
+    options: FighterOptions,
+    #[serde(default)]
+    layout: Layout,
+    stats: Statistics,
+    fights: usize,
+    conversions: usize,
 }
 
-impl<T> Battle<T>
+impl<T, R> Battle<T, R>
 where
-    T: GenerateRandomly + Fighter,
+    T: GenerateRandomly + Fighter + Typed,
+    R: Rng,
 {
     pub fn new(
         img_width: usize,
         img_height: usize,
         selection_algorithm: SelectionAlgorithm,
         filter_fight_candidates: bool,
+        rng: R,
     ) -> Self {
-        let mut rng = rand::thread_rng();
-        let fighters = Grid2D::new_with((img_width, img_height), || T::generate_randomly(&mut rng));
+        Self::with_initializer(
+            img_width,
+            img_height,
+            selection_algorithm,
+            filter_fight_candidates,
+            rng,
+            &Layout::default(),
+        )
+    }
+
+    /// Fills the grid again following the layout and starts counting rounds from 0 again, all
+    /// other settings are kept
+    pub fn regenerate(&mut self) {
+        let mut fighters: Grid2D<T> = self.layout.populate(self.fighters.size(), &mut self.rng);
+        for fighter in fighters.iter_mut() {
+            fighter.configure(&self.options, &mut self.rng);
+        }
+        fighters.set_boundary(self.fighters.boundary());
+        fighters.set_terrain(self.fighters.terrain().to_vec());
+        self.fighters = fighters;
+        self.stats = Statistics::new(T::type_names());
+        self.fights = 0;
+        self.conversions = 0;
+        self.record_stats();
+    }
+}
+
+impl<T, R> Battle<T, R>
+where
+    T: Fighter + Typed,
+    R: Rng,
+{
+    /// A battle whose grid is filled by `initializer` instead of at random
+    pub fn with_initializer<I>(
+        img_width: usize,
+        img_height: usize,
+        selection_algorithm: SelectionAlgorithm,
+        filter_fight_candidates: bool,
+        mut rng: R,
+        initializer: &I,
+    ) -> Self
+    where
+        I: Initializer<T>,
+    {
+        let fighters = initializer.populate((img_width, img_height), &mut rng);
 
-        Self {
+        let mut battle = Self {
             fighters,
             rng,
-            selection_callback: if filter_fight_candidates {
-                match selection_algorithm {
-                    SelectionAlgorithm::WeakestNeighbour => Battle::weakest_neighbour_filtered,
-                    SelectionAlgorithm::RandomNeighbour => Battle::random_neighbour_filtered,
+            selection_algorithm,
+            filter_fight_candidates,
+            neighbourhood: Neighbourhood::default(),
+            update_mode: UpdateMode::default(),
+            reproduction: None,
+            regeneration: None,
+            leveling: false,
+            options: FighterOptions::default(),
+            layout: Layout::default(),
+            stats: Statistics::new(T::type_names()),
+            fights: 0,
+            conversions: 0,
+        };
+        battle.record_stats();
+        battle
+    }
+}
+
+impl<T, R> Battle<T, R> {
+    pub fn fighter(&self, location: Location) -> Option<&T> {
+        self.fighters.get(location)
+    }
+
+    pub fn is_wall(&self, location: Location) -> bool {
+        self.fighters.is_blocked(location)
+    }
+
+    pub fn size(&self) -> Size {
+        self.fighters.size()
+    }
+
+    /// Statistics for every round so far, starting with the initial grid as round 0
+    pub fn stats(&self) -> &Statistics {
+        &self.stats
+    }
+
+    /// Number of rounds played so far
+    pub fn round(&self) -> usize {
+        self.stats.rounds().len() - 1
+    }
+
+    /// Whether only one type is left or nothing was converted for `stalemate_rounds` rounds
+    pub fn outcome(&self, stalemate_rounds: usize) -> Option<Outcome> {
+        self.stats.outcome(stalemate_rounds)
+    }
+
+    pub fn neighbourhood(&self) -> &Neighbourhood {
+        &self.neighbourhood
+    }
+
+    pub fn set_neighbourhood(&mut self, neighbourhood: Neighbourhood) {
+        self.neighbourhood = neighbourhood;
+    }
+
+    pub fn selection_algorithm(&self) -> SelectionAlgorithm {
+        self.selection_algorithm
+    }
+
+    pub fn set_selection_algorithm(&mut self, selection_algorithm: SelectionAlgorithm) {
+        self.selection_algorithm = selection_algorithm;
+    }
+
+    /// Whether fighters only pick neighbours they should fight
+    pub fn filter_fight_candidates(&self) -> bool {
+        self.filter_fight_candidates
+    }
+
+    pub fn set_filter_fight_candidates(&mut self, filter_fight_candidates: bool) {
+        self.filter_fight_candidates = filter_fight_candidates;
+    }
+
+    pub fn update_mode(&self) -> UpdateMode {
+        self.update_mode
+    }
+
+    pub fn set_update_mode(&mut self, update_mode: UpdateMode) {
+        self.update_mode = update_mode;
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.fighters.boundary()
+    }
+
+    /// Chance of a fighter to reproduce into an empty neighbouring cell every round, when set
+    /// defeated fighters leave their cell empty instead of changing their type
+    pub fn reproduction(&self) -> Option<f64> {
+        self.reproduction
+    }
+
+    pub fn set_reproduction(&mut self, reproduction: Option<f64>) {
+        self.reproduction = reproduction;
+    }
+
+    /// Share of the full health every fighter heals at the end of a round, when set
+    pub fn regeneration(&self) -> Option<f64> {
+        self.regeneration
+    }
+
+    pub fn set_regeneration(&mut self, regeneration: Option<f64>) {
+        self.regeneration = regeneration;
+    }
+
+    /// Whether winners gain experience and pass some of it on to the fighters they convert
+    pub fn leveling(&self) -> bool {
+        self.leveling
+    }
+
+    pub fn set_leveling(&mut self, leveling: bool) {
+        self.leveling = leveling;
+    }
+
+    /// Options every fighter of the battle is configured with
+    pub fn options(&self) -> FighterOptions {
+        self.options
+    }
+
+    /// Layout `regenerate` fills the grid with
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn terrain(&self) -> &[bool] {
+        self.fighters.terrain()
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.fighters.set_boundary(boundary);
+    }
+}
+
+impl<T, R> Battle<T, R>
+where
+    T: Fighter + Typed + Clone + Send,
+    R: Rng,
+{
+    pub fn action(&mut self) {
+        match self.update_mode {
+            UpdateMode::Sequential => self.sequential_action(),
+            UpdateMode::Parallel => self.parallel_action(),
+            UpdateMode::Synchronous => self.synchronous_action(),
+        }
+
+        if let Some(rate) = self.regeneration {
+            for fighter in self.fighters.iter_mut() {
+                fighter.regenerate(rate);
+            }
+        }
+
+        self.record_stats();
+    }
+
+    fn sequential_action(&mut self) {
+        let (_, h) = self.fighters.size();
+        let mut sweep = Sweep {
+            fighters: self.fighters.view_mut(),
+            neighbourhood: &self.neighbourhood,
+            selection_algorithm: self.selection_algorithm,
+            filter_fight_candidates: self.filter_fight_candidates,
+            reproduction: self.reproduction,
+            leveling: self.leveling,
+            rng: &mut self.rng,
+            fights: 0,
+            conversions: 0,
+        };
+        sweep.run(0..h);
+
+        self.fights += sweep.fights;
+        self.conversions += sweep.conversions;
+    }
+
+    /// Splits the grid into horizontal bands and first sweeps all even bands at the same time,
+    /// then all odd ones. Bands are at least twice as high as the neighbourhood reaches, so fights
+    /// in two bands that are swept at the same time can never touch the same fighter.
+    fn parallel_action(&mut self) {
+        const BAND_HEIGHT: usize = 16;
+        let (_, h) = self.fighters.size();
+        let reach = self.neighbourhood.radius();
+        let band_height = BAND_HEIGHT.max(2 * reach);
+        let bands: Vec<Range<usize>> = (0..h)
+            .step_by(band_height)
+            .map(|start| start..(start + band_height).min(h))
+            .collect();
+
+        // Every band gets its own generator so the result does not depend on the thread count
+        let seeds: Vec<u64> = bands.iter().map(|_| self.rng.gen()).collect();
+
+        // On a torus, the fighters near the top and bottom edge fight across the edge, so their
+        // bands are not contiguous in memory and are swept on their own afterwards.
+        let torus = self.fighters.boundary() == Boundary::Torus;
+        let wraps = |band: &Range<usize>| torus && (band.start < reach || band.end + reach > h);
+
+        for parity in 0..2 {
+            let (parallel, sequential): (Vec<usize>, Vec<usize>) = (parity..bands.len())
+                .step_by(2)
+                .partition(|&i| !wraps(&bands[i]));
+
+            let regions: Vec<Range<usize>> = parallel
+                .iter()
+                .map(|&i| bands[i].start.saturating_sub(reach)..(bands[i].end + reach).min(h))
+                .collect();
+            let neighbourhood = &self.neighbourhood;
+            let selection_algorithm = self.selection_algorithm;
+            let filter_fight_candidates = self.filter_fight_candidates;
+            let reproduction = self.reproduction;
+            let leveling = self.leveling;
+            let (fights, conversions) = self
+                .fighters
+                .split_rows_mut(&regions)
+                .into_par_iter()
+                .zip(parallel)
+                .map(|(fighters, i)| {
+                    let mut sweep = Sweep {
+                        fighters,
+                        neighbourhood,
+                        selection_algorithm,
+                        filter_fight_candidates,
+                        reproduction,
+                        leveling,
+                        rng: ChaCha8Rng::seed_from_u64(seeds[i]),
+                        fights: 0,
+                        conversions: 0,
+                    };
+                    sweep.run(bands[i].clone());
+                    (sweep.fights, sweep.conversions)
+                })
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+            self.fights += fights;
+            self.conversions += conversions;
+
+            for i in sequential {
+                let mut sweep = Sweep {
+                    fighters: self.fighters.view_mut(),
+                    neighbourhood,
+                    selection_algorithm,
+                    filter_fight_candidates,
+                    reproduction,
+                    leveling,
+                    rng: ChaCha8Rng::seed_from_u64(seeds[i]),
+                    fights: 0,
+                    conversions: 0,
+                };
+                sweep.run(bands[i].clone());
+                self.fights += sweep.fights;
+                self.conversions += sweep.conversions;
+            }
+        }
+    }
+
+    /// Every fighter picks its defender from the previous round and all fights are then applied to
+    /// a copy of the grid. A defender attacked by several fighters only fights the most effective
+    /// of them, ties are broken at random.
+    fn synchronous_action(&mut self) {
+        let (w, _) = self.fighters.size();
+        let mut sweep = Sweep {
+            fighters: self.fighters.view_mut(),
+            neighbourhood: &self.neighbourhood,
+            selection_algorithm: self.selection_algorithm,
+            filter_fight_candidates: self.filter_fight_candidates,
+            reproduction: self.reproduction,
+            leveling: self.leveling,
+            rng: &mut self.rng,
+            fights: 0,
+            conversions: 0,
+        };
+        let attackers = sweep.attackers();
+
+        let mut next = self.fighters.clone();
+        for (i, attacker_loc) in attackers.into_iter().enumerate() {
+            let Some(attacker_loc) = attacker_loc else {
+                continue;
+            };
+            let Some(attacker) = self.fighters.get(attacker_loc) else {
+                continue;
+            };
+            let Some((attacker_cell, cell)) = next.cell_pair_mut(attacker_loc, (i % w, i / w))
+            else {
+                continue;
+            };
+            if let Some(defender) = cell.as_mut() {
+                self.fights += 1;
+                // A defender hits the attacker as it is after this round, which is also the one
+                // that gains experience
+                let (vacate, leveling) = (self.reproduction.is_some(), self.leveling);
+                let rng = &mut self.rng;
+                if counter_strike(defender, attacker_cell, true, vacate, leveling, rng) {
+                    self.conversions += 1;
+                } else if attacker.fight(defender, rng) {
+                    self.conversions += 1;
+                    defeated(attacker_cell.as_mut(), cell, vacate, leveling);
+                } else if counter_strike(defender, attacker_cell, false, vacate, leveling, rng) {
+                    self.conversions += 1;
+                }
+            }
+        }
+
+        // Every empty cell picks one of the fighters around it in the previous round as its parent
+        if let Some(probability) = self.reproduction {
+            let (w, h) = self.fighters.size();
+            for location in (0..h).flat_map(|y| (0..w).map(move |x| (x, y))) {
+                if !next.is_vacant(location) {
+                    continue;
                 }
-            } else {
-                match selection_algorithm {
-                    SelectionAlgorithm::WeakestNeighbour => Battle::weakest_neighbour,
-                    SelectionAlgorithm::RandomNeighbour => Battle::random_neighbour,
+                let parent = self
+                    .neighbourhood
+                    .neighbours(location, (w, h), self.fighters.boundary())
+                    .filter_map(|candidate| self.fighters.get(candidate))
+                    .choose(&mut self.rng);
+                if let Some(parent) = parent {
+                    if self.rng.gen_bool(probability) {
+                        if let Some(cell) = next.cell_mut(location) {
+                            *cell = Some(parent.offspring());
+                        }
+                    }
                 }
-            },
+            }
         }
+        self.fighters = next;
     }
 }
 
-impl<T> Battle<T>
+impl<T, R> Battle<T, R>
 where
-    T: Fighter,
+    T: Fighter + Typed,
+    R: Rng,
 {
-    pub fn fighter(&self, location: Location) -> Option<&T> {
-        self.fighters.get(location)
+    /// Returns true if the defender was defeated, a faster defender can defeat the attacker first
+    pub fn fight(&mut self, attacker_loc: Location, defender_loc: Location) -> bool {
+        let Some((attacker_cell, cell)) = self.fighters.cell_pair_mut(attacker_loc, defender_loc)
+        else {
+            return false;
+        };
+        let (Some(_), Some(defender)) = (attacker_cell.as_ref(), cell.as_mut()) else {
+            return false;
+        };
+
+        self.fights += 1;
+        let (vacate, leveling) = (self.reproduction.is_some(), self.leveling);
+        if counter_strike(
+            defender,
+            attacker_cell,
+            true,
+            vacate,
+            leveling,
+            &mut self.rng,
+        ) {
+            self.conversions += 1;
+            return false;
+        }
+        let Some(attacker) = attacker_cell.as_ref() else {
+            return false;
+        };
+        let is_dead = attacker.fight(defender, &mut self.rng);
+        if is_dead {
+            self.conversions += 1;
+            defeated(attacker_cell.as_mut(), cell, vacate, leveling);
+        } else if counter_strike(
+            defender,
+            attacker_cell,
+            false,
+            vacate,
+            leveling,
+            &mut self.rng,
+        ) {
+            self.conversions += 1;
+        }
+        is_dead
     }
 
-    pub fn action(&mut self) {
+    /// Checks a battle read from a file against the fighter types in use, so a mismatch fails
+    /// right away instead of panicking later
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid_data = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        if !self.fighters.has_valid_size() {
+            return Err(invalid_data("grid cells do not match its size"));
+        }
+        let count = T::type_names().len();
+        if self
+            .fighters
+            .iter()
+            .any(|fighter| fighter.type_index() >= count)
+        {
+            return Err(invalid_data("grid contains fighters of unknown types"));
+        }
+        if [self.reproduction, self.regeneration]
+            .into_iter()
+            .flatten()
+            .any(|value| !is_probability(value))
+        {
+            return Err(invalid_data(
+                "reproduction and regeneration should be between 0 and 1",
+            ));
+        }
+        if self.stats.types().len() != count
+            || self
+                .stats
+                .rounds()
+                .iter()
+                .any(|round| round.counts.len() != count)
+        {
+            return Err(invalid_data("statistics do not match the fighter types"));
+        }
+        Ok(())
+    }
+
+    pub fn set_options(&mut self, options: FighterOptions) {
+        self.options = options;
+        for fighter in self.fighters.iter_mut() {
+            fighter.configure(&options, &mut self.rng);
+        }
+    }
+
+    /// Replaces the fighter at `location` or fills an empty cell, returns false if it is outside
+    /// of the grid or a wall. The counts of the latest round are updated to match.
+    pub fn set_fighter(&mut self, location: Location, mut fighter: T) -> bool {
+        let Some(cell) = self.fighters.cell_mut(location) else {
+            return false;
+        };
+        fighter.configure(&self.options, &mut self.rng);
+        let added = fighter.type_index();
+        let removed = cell.replace(fighter).map(|old| old.type_index());
+        if let Some(latest) = self.stats.latest() {
+            let mut counts = latest.counts.clone();
+            if let Some(removed) = removed {
+                counts[removed] -= 1;
+            }
+            counts[added] += 1;
+            self.stats.set_latest_counts(counts);
+        }
+        true
+    }
+
+    /// Marks cells as impassable walls that never fight, indexed row by row like the grid. An empty
+    /// list removes all walls.
+    pub fn set_terrain(&mut self, blocked: Vec<bool>) {
+        self.fighters.set_terrain(blocked);
+        let counts = self.counts();
+        self.stats.set_latest_counts(counts);
+    }
+
+    fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.stats.types().len()];
+        for fighter in self.fighters.iter() {
+            counts[fighter.type_index()] += 1;
+        }
+        counts
+    }
+
+    fn record_stats(&mut self) {
+        self.stats.record(RoundStats {
+            round: self.stats.rounds().len(),
+            fights: self.fights,
+            conversions: self.conversions,
+            counts: self.counts(),
+        });
+        self.fights = 0;
+        self.conversions = 0;
+    }
+}
+
+/// Lets every fighter in some rows of the grid attack one of its neighbours
+struct Sweep<'a, T, R> {
+    fighters: GridView<'a, T>,
+    neighbourhood: &'a Neighbourhood,
+    selection_algorithm: SelectionAlgorithm,
+    filter_fight_candidates: bool,
+    reproduction: Option<f64>,
+    leveling: bool,
+    rng: R,
+    fights: usize,
+    conversions: usize,
+}
+
+impl<'a, T, R> Sweep<'a, T, R>
+where
+    T: Fighter + Typed,
+    R: Rng,
+{
+    fn run(&mut self, rows: Range<usize>) {
         // We use prime numbers as offsets to loop through the entries in a semi-random fashion.
         // These particular prime numbers have been chosen by a fair dice roll.
         const PRIMES: &[usize] = &[48817, 58099, 89867, 105407, 126943, 200723, 221021, 231677];
-        let (w, h) = self.fighters.size();
-        let num_entries = self.fighters.count();
+        let size = self.fighters.size();
+        let w = size.0;
+        let num_entries = rows.len() * w;
 
         let start = self.rng.gen_range(0..num_entries);
         let offset = PRIMES[self.rng.gen_range(0..PRIMES.len())];
         let mut current = start;
 
         loop {
-            let attacker_loc = (current % w, current / w);
-            let defender_loc = (self.selection_callback)(self, attacker_loc, (w, h));
+            let attacker_loc = (current % w, rows.start + current / w);
+            let defender_loc = self.select_defender(attacker_loc, size);
             if let Some(defender_loc) = defender_loc {
                 self.fight(attacker_loc, defender_loc);
             }
+            if let Some(probability) = self.reproduction {
+                self.reproduce(attacker_loc, size, probability);
+            }
 
             current = (current + offset) % num_entries;
             if current == start {
@@ -87,16 +673,122 @@ where
         }
     }
 
-    pub fn fight(&mut self, attacker_loc: Location, defender_loc: Location) {
-        if let Some((attacker, defender)) = self.fighters.get_pair_mut(attacker_loc, defender_loc) {
-            attacker.fight(defender);
+    /// Lets every fighter pick a defender without fighting yet and returns the attacker that wins
+    /// the conflict for each defender, indexed like the grid
+    fn attackers(&mut self) -> Vec<Option<Location>> {
+        let size = self.fighters.size();
+        let (w, h) = size;
+        // Attacker, its effectiveness and how many attackers were tied with it so far
+        let mut attackers: Vec<Option<(Location, i32, u32)>> = vec![None; w * h];
+
+        for y in 0..h {
+            for x in 0..w {
+                let attacker_loc = (x, y);
+                let Some(defender_loc) = self.select_defender(attacker_loc, size) else {
+                    continue;
+                };
+                let (Some(attacker), Some(defender)) = (
+                    self.fighters.get(attacker_loc),
+                    self.fighters.get(defender_loc),
+                ) else {
+                    continue;
+                };
+
+                let effectiveness = attacker.get_effectiveness(defender);
+                let entry = &mut attackers[defender_loc.1 * w + defender_loc.0];
+                *entry = match *entry {
+                    Some((_, best, _)) if best > effectiveness => *entry,
+                    Some((current, best, ties)) if best == effectiveness => {
+                        // Picks every tied attacker with equal probability
+                        if self.rng.gen_range(0..=ties) == 0 {
+                            Some((attacker_loc, best, ties + 1))
+                        } else {
+                            Some((current, best, ties + 1))
+                        }
+                    }
+                    _ => Some((attacker_loc, effectiveness, 1)),
+                };
+            }
+        }
+
+        attackers
+            .into_iter()
+            .map(|entry| entry.map(|(location, _, _)| location))
+            .collect()
+    }
+
+    fn fight(&mut self, attacker_loc: Location, defender_loc: Location) {
+        let Some((attacker_cell, cell)) = self.fighters.cell_pair_mut(attacker_loc, defender_loc)
+        else {
+            return;
+        };
+        let (Some(_), Some(defender)) = (attacker_cell.as_ref(), cell.as_mut()) else {
+            return;
+        };
+
+        self.fights += 1;
+        let (vacate, leveling) = (self.reproduction.is_some(), self.leveling);
+        if counter_strike(
+            defender,
+            attacker_cell,
+            true,
+            vacate,
+            leveling,
+            &mut self.rng,
+        ) {
+            self.conversions += 1;
+        } else if let Some(attacker) = attacker_cell.as_ref() {
+            if attacker.fight(defender, &mut self.rng) {
+                self.conversions += 1;
+                defeated(attacker_cell.as_mut(), cell, vacate, leveling);
+            } else if counter_strike(
+                defender,
+                attacker_cell,
+                false,
+                vacate,
+                leveling,
+                &mut self.rng,
+            ) {
+                self.conversions += 1;
+            }
+        }
+    }
+
+    /// Puts a fresh fighter of the same type into an empty neighbouring cell
+    fn reproduce(&mut self, origin: Location, size: Size, probability: f64) {
+        if self.fighters.get(origin).is_none() || !self.rng.gen_bool(probability) {
+            return;
+        }
+
+        let fighters = &self.fighters;
+        let target = self
+            .neighbourhood
+            .neighbours(origin, size, fighters.boundary())
+            .filter(|&candidate| fighters.is_vacant(candidate))
+            .choose(&mut self.rng);
+        let child = self.fighters.get(origin).map(Typed::offspring);
+        if let Some(cell) = target.and_then(|target| self.fighters.cell_mut(target)) {
+            *cell = child;
+        }
+    }
+
+    fn select_defender(&mut self, origin: Location, size: Size) -> Option<Location> {
+        match (self.selection_algorithm, self.filter_fight_candidates) {
+            (SelectionAlgorithm::WeakestNeighbour, false) => self.weakest_neighbour(origin, size),
+            (SelectionAlgorithm::WeakestNeighbour, true) => {
+                self.weakest_neighbour_filtered(origin, size)
+            }
+            (SelectionAlgorithm::RandomNeighbour, false) => self.random_neighbour(origin, size),
+            (SelectionAlgorithm::RandomNeighbour, true) => {
+                self.random_neighbour_filtered(origin, size)
+            }
         }
     }
 
     fn weakest_neighbour(&mut self, origin: Location, size: Size) -> Option<Location> {
         let fighter = self.fighters.get(origin)?;
-        neighbours(origin, size)
-            .into_iter()
+        self.neighbourhood
+            .neighbours(origin, size, self.fighters.boundary())
             .filter_map(|candidate| get_candidate(&self.fighters, candidate))
             .max_by_key(|(neighbour, _)| fighter.get_effectiveness(neighbour))
             .map(|(_, candidate)| candidate)
@@ -104,9 +796,8 @@ where
 
     fn weakest_neighbour_filtered(&mut self, origin: Location, size: Size) -> Option<Location> {
         let fighter = self.fighters.get(origin)?;
-        neighbours(origin, size)
-            .into_iter()
-            .into_iter()
+        self.neighbourhood
+            .neighbours(origin, size, self.fighters.boundary())
             .filter_map(|candidate| get_candidate(&self.fighters, candidate))
             .filter(|(neighbour, _)| fighter.should_fight(neighbour))
             .max_by_key(|(neighbour, _)| fighter.get_effectiveness(neighbour))
@@ -114,13 +805,24 @@ where
     }
 
     fn random_neighbour(&mut self, origin: Location, size: Size) -> Option<Location> {
-        neighbours(origin, size).into_iter().choose(&mut self.rng)
+        let neighbours = self
+            .neighbourhood
+            .neighbours(origin, size, self.fighters.boundary());
+        // Filtering changes how the neighbour is picked, so only do it when there are walls
+        if self.fighters.has_terrain() {
+            let fighters = &self.fighters;
+            neighbours
+                .filter(|&candidate| fighters.get(candidate).is_some())
+                .choose(&mut self.rng)
+        } else {
+            neighbours.choose(&mut self.rng)
+        }
     }
 
     fn random_neighbour_filtered(&mut self, origin: Location, size: Size) -> Option<Location> {
         let fighter = self.fighters.get(origin)?;
-        neighbours(origin, size)
-            .into_iter()
+        self.neighbourhood
+            .neighbours(origin, size, self.fighters.boundary())
             .filter_map(|candidate| get_candidate(&self.fighters, candidate))
             .filter(|(neighbour, _)| fighter.should_fight(neighbour))
             .choose(&mut self.rng)
@@ -128,16 +830,591 @@ where
     }
 }
 
-pub fn neighbours((x, y): Location, (w, h): Size) -> [Location; 4] {
-    [
-        (x, (y + h - 1) % h),
-        ((x + 1) % w, y),
-        (x, (y + 1) % h),
-        ((x + w - 1) % w, y),
-    ]
-}
-
-fn get_candidate<T>(grid: &Grid2D<T>, location: Location) -> Option<(&T, Location)> {
+fn get_candidate<'a, T>(grid: &'a GridView<T>, location: Location) -> Option<(&'a T, Location)> {
     let item = grid.get(location)?;
     Some((item, location))
 }
+
+/// Lets a defender hit its attacker, `before_attack` if it strikes first and otherwise after
+/// surviving the attack if it strikes back. Returns true if that defeated the attacker.
+fn counter_strike<T, R>(
+    defender: &mut T,
+    attacker_cell: &mut Option<T>,
+    before_attack: bool,
+    vacate: bool,
+    leveling: bool,
+    rng: &mut R,
+) -> bool
+where
+    T: Fighter,
+    R: Rng,
+{
+    let Some(attacker) = attacker_cell.as_mut() else {
+        return false;
+    };
+    let strikes = if before_attack {
+        defender.strikes_first(attacker)
+    } else {
+        !defender.strikes_first(attacker) && defender.strikes_back(attacker)
+    };
+    if !strikes || !defender.should_fight(attacker) {
+        return false;
+    }
+    let is_dead = defender.fight(attacker, rng);
+    if is_dead {
+        defeated(Some(defender), attacker_cell, vacate, leveling);
+    }
+    is_dead
+}
+
+/// Whether `value` can be used as the chance of something to happen
+pub(crate) fn is_probability(value: f64) -> bool {
+    (0.0..=1.0).contains(&value)
+}
+
+/// Cleans up after `winner` defeated the fighter in `loser_cell`. With `vacate` the cell is
+/// emptied, with `leveling` the winner gains experience and passes some of it on to the fighter
+/// it converted.
+fn defeated<T>(winner: Option<&mut T>, loser_cell: &mut Option<T>, vacate: bool, leveling: bool)
+where
+    T: Fighter,
+{
+    if vacate {
+        *loser_cell = None;
+    }
+    if let (true, Some(winner)) = (leveling, winner) {
+        winner.gain_experience();
+        if let Some(loser) = loser_cell.as_mut() {
+            loser.inherit_experience(winner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::LayoutKind;
+    use crate::pokemon::{Pokemon, PokemonType};
+    use crate::render::{render, RenderMode};
+    use crate::rps::{RPSType, RPS};
+    use crate::street_fighter::StreetFighter;
+    use crate::types::Colored;
+    use image::RgbImage;
+    use rand::SeedableRng;
+
+    fn run<T>(seed: u64, selection_algorithm: SelectionAlgorithm, rounds: usize) -> Vec<u8>
+    where
+        T: Colored + Fighter + GenerateRandomly + Typed + Clone + Send,
+    {
+        let rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut battle = Battle::<T>::new(64, 48, selection_algorithm, true, rng);
+        for _ in 0..rounds {
+            battle.action();
+        }
+
+        let mut image = RgbImage::new(64, 48);
+        render(&battle, &mut image, RenderMode::Type);
+        image.into_raw()
+    }
+
+    #[test]
+    fn test_same_seed_is_reproducible() {
+        for algorithm in [
+            SelectionAlgorithm::WeakestNeighbour,
+            SelectionAlgorithm::RandomNeighbour,
+        ] {
+            assert_eq!(
+                run::<Pokemon>(7, algorithm, 20),
+                run::<Pokemon>(7, algorithm, 20)
+            );
+            assert_eq!(
+                run::<StreetFighter>(7, algorithm, 20),
+                run::<StreetFighter>(7, algorithm, 20)
+            );
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_diverge() {
+        assert_ne!(
+            run::<StreetFighter>(1, SelectionAlgorithm::RandomNeighbour, 20),
+            run::<StreetFighter>(2, SelectionAlgorithm::RandomNeighbour, 20)
+        );
+    }
+
+    #[test]
+    fn test_parallel_does_not_depend_on_thread_count() {
+        let run_parallel = |threads, boundary| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let rng = ChaCha8Rng::seed_from_u64(3);
+                let mut battle =
+                    Battle::<Pokemon>::new(64, 96, SelectionAlgorithm::WeakestNeighbour, true, rng);
+                battle.set_update_mode(UpdateMode::Parallel);
+                battle.set_boundary(boundary);
+                for _ in 0..10 {
+                    battle.action();
+                }
+
+                assert!(battle.stats().latest().unwrap().fights > 0);
+                let mut image = RgbImage::new(64, 96);
+                render(&battle, &mut image, RenderMode::Type);
+                image.into_raw()
+            })
+        };
+
+        for boundary in [Boundary::Torus, Boundary::Walls] {
+            assert_eq!(run_parallel(1, boundary), run_parallel(4, boundary));
+        }
+    }
+
+    #[test]
+    fn test_synchronous() {
+        let run_synchronous = || {
+            let rng = ChaCha8Rng::seed_from_u64(9);
+            let mut battle = Battle::<StreetFighter>::new(
+                32,
+                24,
+                SelectionAlgorithm::RandomNeighbour,
+                true,
+                rng,
+            );
+            battle.set_update_mode(UpdateMode::Synchronous);
+            for _ in 0..10 {
+                battle.action();
+            }
+            battle
+        };
+
+        let battle = run_synchronous();
+        for round in battle.stats().rounds() {
+            // Every fighter is attacked at most once per round
+            assert!(round.fights <= 32 * 24);
+            assert_eq!(round.counts.iter().sum::<usize>(), 32 * 24);
+        }
+        assert!(battle.stats().latest().unwrap().fights > 0);
+
+        let mut expected = RgbImage::new(32, 24);
+        let mut actual = RgbImage::new(32, 24);
+        render(&battle, &mut expected, RenderMode::Type);
+        render(&run_synchronous(), &mut actual, RenderMode::Type);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_stats() {
+        let rng = ChaCha8Rng::seed_from_u64(5);
+        let mut battle =
+            Battle::<Pokemon>::new(32, 32, SelectionAlgorithm::WeakestNeighbour, true, rng);
+        for _ in 0..5 {
+            battle.action();
+        }
+
+        let stats = battle.stats();
+        assert_eq!(stats.types().len(), 18);
+        assert_eq!(stats.rounds().len(), 6);
+        assert_eq!(stats.rounds()[0].fights, 0);
+        for (i, round) in stats.rounds().iter().enumerate() {
+            assert_eq!(round.round, i);
+            assert_eq!(round.counts.iter().sum::<usize>(), 32 * 32);
+            assert!(round.conversions <= round.fights);
+        }
+        assert!(stats.latest().unwrap().fights > 0);
+    }
+
+    #[test]
+    fn test_outcome_and_regenerate() {
+        let rng = ChaCha8Rng::seed_from_u64(2);
+        let mut battle =
+            Battle::<RPS>::new(32, 32, SelectionAlgorithm::WeakestNeighbour, true, rng);
+        battle.set_boundary(Boundary::Walls);
+        for x in 0..32 {
+            for y in 0..32 {
+                battle.set_fighter((x, y), RPS::new(RPSType::Rock));
+            }
+        }
+        // Painting is visible in the statistics before the next round
+        assert_eq!(battle.stats().latest().unwrap().counts, [1024, 0, 0]);
+        battle.action();
+        assert_eq!(
+            battle.outcome(10),
+            Some(Outcome::Extinction(RPSType::Rock as usize))
+        );
+
+        battle.regenerate();
+        assert_eq!(battle.round(), 0);
+        assert_eq!(battle.boundary(), Boundary::Walls);
+        assert_eq!(battle.outcome(10), None);
+        assert!(battle
+            .stats()
+            .latest()
+            .unwrap()
+            .counts
+            .iter()
+            .all(|&c| c > 0));
+
+        battle.set_layout(Layout {
+            kind: LayoutKind::Seeds,
+            ..Layout::default()
+        });
+        battle.regenerate();
+        assert_eq!(battle.stats().latest().unwrap().counts, [1, 1, 1]);
+    }
+
+    #[test]
+    fn test_terrain() {
+        for update_mode in [
+            UpdateMode::Sequential,
+            UpdateMode::Parallel,
+            UpdateMode::Synchronous,
+        ] {
+            let rng = ChaCha8Rng::seed_from_u64(4);
+            let mut battle =
+                Battle::<RPS>::new(32, 32, SelectionAlgorithm::RandomNeighbour, false, rng);
+            battle.set_update_mode(update_mode);
+            battle.set_boundary(Boundary::Walls);
+            for x in 0..32 {
+                for y in 0..32 {
+                    let kind = if x < 16 {
+                        RPSType::Rock
+                    } else {
+                        RPSType::Paper
+                    };
+                    battle.set_fighter((x, y), RPS::new(kind));
+                }
+            }
+            // A wall down the middle keeps rock safe from paper
+            battle.set_terrain((0..32 * 32).map(|i| i % 32 == 16).collect());
+            assert_eq!(battle.stats().latest().unwrap().counts, [512, 480, 0]);
+
+            for _ in 0..10 {
+                battle.action();
+            }
+            assert!(battle.fighter((16, 5)).is_none());
+            assert!(!battle.set_fighter((16, 5), RPS::new(RPSType::Rock)));
+            assert_eq!(battle.stats().latest().unwrap().counts, [512, 480, 0]);
+            assert!(battle.stats().latest().unwrap().fights > 0);
+        }
+    }
+
+    #[test]
+    fn test_reproduction() {
+        for update_mode in [UpdateMode::Sequential, UpdateMode::Synchronous] {
+            let layout = Layout {
+                kind: LayoutKind::Seeds,
+                ..Layout::default()
+            };
+            let rng = ChaCha8Rng::seed_from_u64(6);
+            let mut battle = Battle::<RPS>::with_initializer(
+                32,
+                32,
+                SelectionAlgorithm::WeakestNeighbour,
+                true,
+                rng,
+                &layout,
+            );
+            battle.set_update_mode(update_mode);
+            battle.set_reproduction(Some(0.5));
+            assert_eq!(battle.stats().latest().unwrap().counts, [1, 1, 1]);
+
+            for _ in 0..60 {
+                battle.action();
+            }
+            let stats = battle.stats();
+            let population = |round: &RoundStats| round.counts.iter().sum::<usize>();
+            assert!(population(stats.latest().unwrap()) > 100);
+            // Defeated fighters leave empty cells behind
+            assert!(stats.rounds().iter().any(|round| round.conversions > 0));
+            assert!(stats
+                .rounds()
+                .iter()
+                .all(|round| population(round) < 32 * 32));
+        }
+    }
+
+    #[test]
+    fn test_regeneration() {
+        let mut battle = Battle::<Pokemon>::new(
+            32,
+            32,
+            SelectionAlgorithm::WeakestNeighbour,
+            true,
+            ChaCha8Rng::seed_from_u64(2),
+        );
+        let fighters = |battle: &Battle<Pokemon>| {
+            (0..32 * 32)
+                .filter_map(|i| battle.fighter((i % 32, i / 32)))
+                .map(|fighter| fighter.health())
+                .collect::<Vec<_>>()
+        };
+        battle.action();
+        assert!(fighters(&battle).iter().any(|&health| health < 1.0));
+
+        let mut type_colors = RgbImage::new(32, 32);
+        let mut health_colors = RgbImage::new(32, 32);
+        render(&battle, &mut type_colors, RenderMode::Type);
+        render(&battle, &mut health_colors, RenderMode::Health);
+        assert_ne!(type_colors, health_colors);
+        assert!(type_colors
+            .pixels()
+            .zip(health_colors.pixels())
+            .all(|(full, dimmed)| (0..3).all(|i| dimmed[i] <= full[i])));
+
+        battle.set_regeneration(Some(1.0));
+        battle.action();
+        assert!(fighters(&battle).iter().all(|&health| health == 1.0));
+    }
+
+    #[test]
+    fn test_base_stats() {
+        let new_battle = || {
+            Battle::<Pokemon>::new(
+                32,
+                32,
+                SelectionAlgorithm::WeakestNeighbour,
+                true,
+                ChaCha8Rng::seed_from_u64(3),
+            )
+        };
+        let counts = |battle: &Battle<Pokemon>| battle.stats().latest().unwrap().counts.clone();
+        let mut plain = new_battle();
+        let mut battle = new_battle();
+        battle.set_options(FighterOptions {
+            base_stats: true,
+            ..FighterOptions::default()
+        });
+        assert!((0..32 * 32)
+            .filter_map(|i| battle.fighter((i % 32, i / 32)))
+            .all(|fighter| fighter.health() == 1.0));
+        for _ in 0..10 {
+            plain.action();
+            battle.action();
+        }
+        assert_ne!(counts(&plain), counts(&battle));
+
+        // A snapshot continues with base stats
+        let json = serde_json::to_string(&battle).unwrap();
+        let mut restored: Battle<Pokemon> = serde_json::from_str(&json).unwrap();
+        assert!(restored.options().base_stats);
+        for _ in 0..10 {
+            battle.action();
+            restored.action();
+        }
+        assert_eq!(counts(&battle), counts(&restored));
+    }
+
+    #[test]
+    fn test_dual_types() {
+        let blobs = Layout {
+            kind: LayoutKind::Blobs,
+            ..Layout::default()
+        };
+        let mut battle = Battle::<Pokemon>::with_initializer(
+            32,
+            32,
+            SelectionAlgorithm::WeakestNeighbour,
+            true,
+            ChaCha8Rng::seed_from_u64(4),
+            &blobs,
+        );
+        battle.set_layout(blobs);
+        let dual = |battle: &Battle<Pokemon>| {
+            (0..32 * 32)
+                .filter_map(|i| battle.fighter((i % 32, i / 32)))
+                .filter(|fighter| fighter.to_string().contains('/'))
+                .count()
+        };
+        assert_eq!(dual(&battle), 0);
+
+        let options = FighterOptions {
+            dual_types: true,
+            ..FighterOptions::default()
+        };
+        battle.set_options(options);
+        assert!((256..768).contains(&dual(&battle)));
+        battle.regenerate();
+        assert!((256..768).contains(&dual(&battle)));
+
+        // Painted fighters get a second type as well
+        for x in 0..32 {
+            battle.set_fighter((x, 0), Pokemon::new(PokemonType::Fire));
+        }
+        assert!((0..32)
+            .filter_map(|x| battle.fighter((x, 0)))
+            .any(|fighter| fighter.to_string().starts_with("Fire/")));
+
+        battle.set_options(FighterOptions::default());
+        assert_eq!(dual(&battle), 0);
+    }
+
+    /// Defeats every defender in one hit, the faster of two strikes first and the slower one
+    /// strikes back
+    struct Racer {
+        kind: usize,
+        speed: u32,
+        wins: u32,
+    }
+
+    impl Racer {
+        fn new(kind: usize, speed: u32) -> Self {
+            Racer {
+                kind,
+                speed,
+                wins: 0,
+            }
+        }
+    }
+
+    impl Fighter for Racer {
+        fn should_fight(&self, defender: &Self) -> bool {
+            self.kind != defender.kind
+        }
+
+        fn get_effectiveness(&self, _defender: &Self) -> i32 {
+            100
+        }
+
+        fn fight<R>(&self, defender: &mut Self, _rng: &mut R) -> bool
+        where
+            R: Rng,
+        {
+            defender.kind = self.kind;
+            true
+        }
+
+        fn strikes_first(&self, attacker: &Self) -> bool {
+            self.speed > attacker.speed
+        }
+
+        fn strikes_back(&self, _attacker: &Self) -> bool {
+            true
+        }
+
+        fn level(&self) -> u32 {
+            1 + self.wins
+        }
+
+        fn gain_experience(&mut self) {
+            self.wins += 1;
+        }
+
+        fn inherit_experience(&mut self, winner: &Self) {
+            self.wins = winner.wins / 2;
+        }
+    }
+
+    #[test]
+    fn test_counter_strike() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut slow = Racer::new(0, 1);
+        let mut fast = Racer::new(1, 2);
+
+        let mut attacker = Some(Racer::new(2, 1));
+        assert!(!counter_strike(
+            &mut slow,
+            &mut attacker,
+            true,
+            false,
+            false,
+            &mut rng
+        ));
+        assert_eq!(attacker.as_ref().unwrap().kind, 2);
+        assert!(counter_strike(
+            &mut fast,
+            &mut attacker,
+            true,
+            false,
+            false,
+            &mut rng
+        ));
+        assert_eq!(attacker.as_ref().unwrap().kind, 1);
+        // Fighters of the same kind never fight
+        assert!(!counter_strike(
+            &mut fast,
+            &mut attacker,
+            true,
+            false,
+            false,
+            &mut rng
+        ));
+
+        let mut attacker = Some(Racer::new(0, 1));
+        assert!(counter_strike(
+            &mut fast,
+            &mut attacker,
+            true,
+            true,
+            false,
+            &mut rng
+        ));
+        assert!(attacker.is_none());
+
+        // A slower defender only strikes back after surviving the attack
+        let mut attacker = Some(Racer::new(2, 1));
+        assert!(!counter_strike(
+            &mut fast,
+            &mut attacker,
+            false,
+            false,
+            false,
+            &mut rng
+        ));
+        assert!(counter_strike(
+            &mut slow,
+            &mut attacker,
+            false,
+            false,
+            false,
+            &mut rng
+        ));
+        assert_eq!(attacker.as_ref().unwrap().kind, 0);
+    }
+
+    #[test]
+    fn test_leveling() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut fast = Racer::new(1, 2);
+        for kind in [2, 3, 4, 5] {
+            let mut attacker = Some(Racer::new(kind, 1));
+            assert!(counter_strike(
+                &mut fast,
+                &mut attacker,
+                true,
+                false,
+                true,
+                &mut rng
+            ));
+            assert_eq!(attacker.unwrap().level(), 1 + fast.wins / 2);
+        }
+        assert_eq!(fast.level(), 5);
+
+        // Nobody is left to take over experience in an empty cell
+        let mut loser = Some(Racer::new(0, 1));
+        defeated(Some(&mut fast), &mut loser, true, true);
+        assert!(loser.is_none());
+        assert_eq!(fast.level(), 6);
+
+        let mut battle = Battle::<Pokemon>::new(
+            32,
+            32,
+            SelectionAlgorithm::WeakestNeighbour,
+            true,
+            ChaCha8Rng::seed_from_u64(2),
+        );
+        let max_level = |battle: &Battle<Pokemon>| {
+            (0..32 * 32)
+                .filter_map(|i| battle.fighter((i % 32, i / 32)))
+                .map(Fighter::level)
+                .max()
+                .unwrap()
+        };
+        (0..10).for_each(|_| battle.action());
+        assert_eq!(max_level(&battle), 1);
+        battle.set_leveling(true);
+        (0..10).for_each(|_| battle.action());
+        assert!(max_level(&battle) > 1);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::grid::Size;
+
+type Location = (usize, usize);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BrushShape {
+    Square,
+    Circle,
+}
+
+/// The cells covered when painting fighters onto the grid
+#[derive(Clone, Copy, Debug)]
+pub struct Brush {
+    pub shape: BrushShape,
+    /// Radius 0 paints a single cell
+    pub radius: usize,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            shape: BrushShape::Circle,
+            radius: 4,
+        }
+    }
+}
+
+impl Brush {
+    /// Cells covered by the brush centered on `center`, without the ones outside of the grid
+    pub fn cells(&self, (x, y): Location, (w, h): Size) -> impl Iterator<Item = Location> {
+        let r = self.radius as isize;
+        let shape = self.shape;
+        (-r..=r)
+            .flat_map(move |dy| (-r..=r).map(move |dx| (dx, dy)))
+            .filter(move |&(dx, dy)| match shape {
+                BrushShape::Square => true,
+                BrushShape::Circle => dx * dx + dy * dy <= r * r,
+            })
+            .map(move |(dx, dy)| (x as isize + dx, y as isize + dy))
+            .filter(move |&(x, y)| x >= 0 && y >= 0 && x < w as isize && y < h as isize)
+            .map(|(x, y)| (x as usize, y as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        let square = Brush {
+            shape: BrushShape::Square,
+            radius: 1,
+        };
+        assert_eq!(square.cells((5, 5), (10, 10)).count(), 9);
+        assert_eq!(
+            square.cells((0, 0), (10, 10)).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+
+        let circle = Brush {
+            shape: BrushShape::Circle,
+            radius: 2,
+        };
+        let cells: Vec<_> = circle.cells((5, 5), (10, 10)).collect();
+        assert_eq!(cells.len(), 13);
+        assert!(cells.contains(&(5, 3)));
+        assert!(!cells.contains(&(3, 3)));
+
+        let dot = Brush {
+            shape: BrushShape::Circle,
+            radius: 0,
+        };
+        assert_eq!(
+            dot.cells((9, 9), (10, 10)).collect::<Vec<_>>(),
+            vec![(9, 9)]
+        );
+    }
+}
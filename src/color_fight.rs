@@ -1,8 +1,9 @@
 use crate::battle::Fighter;
-use crate::types::{Colored, GenerateRandomly};
+use crate::types::{Colored, GenerateRandomly, Typed};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct ColorFighter {
     color: [u8; 3],
 }
@@ -31,7 +32,7 @@ impl ColorFighter {
             }
         }
 
-        self.color.iter().any(|c| *c == 0)
+        self.color.contains(&0)
     }
 }
 
@@ -50,7 +51,10 @@ impl Fighter for ColorFighter {
         1
     }
 
-    fn fight(&self, defender: &mut Self) -> bool {
+    fn fight<R>(&self, defender: &mut Self, _rng: &mut R) -> bool
+    where
+        R: Rng,
+    {
         let _effectiveness = self.get_effectiveness(defender);
 
         let is_dead = defender.take_damage(&self.color);
@@ -76,8 +80,35 @@ impl GenerateRandomly for ColorFighter {
 }
 
 impl Colored for ColorFighter {
-    fn color(&self) -> nannou::image::Rgb<u8> {
-        self.color.into()
+    fn color(&self) -> [u8; 3] {
+        self.color
+    }
+}
+
+// Color fighters have no fixed types, so they are grouped by their strongest channel
+impl Typed for ColorFighter {
+    fn type_index(&self) -> usize {
+        let mut index = 0;
+        for (i, c) in self.color.iter().enumerate() {
+            if *c > self.color[index] {
+                index = i;
+            }
+        }
+        index
+    }
+
+    fn type_names() -> Vec<String> {
+        vec!["Red".to_string(), "Green".to_string(), "Blue".to_string()]
+    }
+
+    fn from_type_index(index: usize) -> Self {
+        let mut s = Self::new();
+        s.color[index] = 255;
+        s
+    }
+
+    fn from_color(color: [u8; 3], _closest_type: usize) -> Self {
+        ColorFighter { color }
     }
 }
 
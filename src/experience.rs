@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_LEVEL: u32 = 10;
+const POINTS_PER_LEVEL: u32 = 3;
+/// Share of its experience, in percent, a winner passes on to the fighter it converted
+const INHERITED_PERCENT: u32 = 50;
+/// Damage and health gained per level, in percent of the values at level 1
+const BONUS_PER_LEVEL: i32 = 10;
+
+/// Points a fighter collects by winning fights
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct Experience {
+    points: u32,
+}
+
+impl Experience {
+    pub fn level(&self) -> u32 {
+        (1 + self.points / POINTS_PER_LEVEL).min(MAX_LEVEL)
+    }
+
+    /// Adds the experience of one won fight
+    pub fn gain(&mut self) {
+        self.points = self.points.saturating_add(1);
+    }
+
+    /// The experience a fighter converted by `winner` starts with
+    pub fn inherited(winner: &Experience) -> Self {
+        Experience {
+            points: winner.points * INHERITED_PERCENT / 100,
+        }
+    }
+
+    /// Scales a damage or health value at level 1 to the current level
+    pub fn scale(&self, value: i32) -> i32 {
+        value * (100 + BONUS_PER_LEVEL * (self.level() as i32 - 1)) / 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let mut experience = Experience::default();
+        assert_eq!(experience.level(), 1);
+        assert_eq!(experience.scale(80), 80);
+
+        (0..7).for_each(|_| experience.gain());
+        assert_eq!(experience.level(), 3);
+        assert_eq!(experience.scale(80), 96);
+        assert_eq!(Experience::inherited(&experience).level(), 2);
+
+        (0..100).for_each(|_| experience.gain());
+        assert_eq!(experience.level(), MAX_LEVEL);
+    }
+}
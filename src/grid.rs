@@ -1,59 +1,353 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::iter::repeat_with;
+use std::ops::Range;
 
 pub type Size = (usize, usize);
 
+/// What happens to neighbours that would lie beyond the edge of the grid
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Boundary {
+    /// Wraps around all edges
+    #[default]
+    Torus,
+    /// Edges are walls, so cells next to them have fewer neighbours
+    Walls,
+    /// Neighbours beyond an edge are mirrored back into the grid
+    Reflect,
+    /// Wraps around the left and right edges, top and bottom are walls
+    Cylinder,
+}
+
+#[derive(Clone, Copy)]
+enum Edge {
+    Wrap,
+    Wall,
+    Reflect,
+}
+
+impl Boundary {
+    /// Location at `offset` from `(x, y)`, or None if it lies beyond a wall
+    pub fn apply(
+        self,
+        (x, y): (usize, usize),
+        (dx, dy): (isize, isize),
+        (w, h): Size,
+    ) -> Option<(usize, usize)> {
+        let (horizontal, vertical) = match self {
+            Boundary::Torus => (Edge::Wrap, Edge::Wrap),
+            Boundary::Walls => (Edge::Wall, Edge::Wall),
+            Boundary::Reflect => (Edge::Reflect, Edge::Reflect),
+            Boundary::Cylinder => (Edge::Wrap, Edge::Wall),
+        };
+
+        Some((horizontal.apply(x, dx, w)?, vertical.apply(y, dy, h)?))
+    }
+}
+
+impl Edge {
+    fn apply(self, position: usize, offset: isize, length: usize) -> Option<usize> {
+        let length = length as isize;
+        let target = position as isize + offset;
+        let target = match self {
+            Edge::Wrap => target.rem_euclid(length),
+            Edge::Wall => target,
+            Edge::Reflect if target < 0 => -target,
+            Edge::Reflect if target >= length => 2 * (length - 1) - target,
+            Edge::Reflect => target,
+        };
+
+        // Reflecting can still end up outside when the offset is larger than the grid
+        (0..length).contains(&target).then_some(target as usize)
+    }
+}
+
+/// Cells hold at most one item each, so they can also be empty
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Grid2D<T> {
-    data: Vec<T>,
+    data: Vec<Option<T>>,
     width: usize,
     height: usize,
+    #[serde(default)]
+    boundary: Boundary,
+    /// Impassable cells, empty when the grid has no terrain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    blocked: Vec<bool>,
 }
 
 impl<T> Grid2D<T> {
-    pub fn new_with<F>((width, height): Size, generator: F) -> Self
+    pub fn new_with<F>((width, height): Size, mut generator: F) -> Self
     where
         F: FnMut() -> T,
     {
-        let data = repeat_with(generator).take(width * height).collect();
+        let data = repeat_with(|| Some(generator()))
+            .take(width * height)
+            .collect();
+
+        Grid2D {
+            data,
+            width,
+            height,
+            boundary: Boundary::default(),
+            blocked: Vec::new(),
+        }
+    }
+
+    /// Grid where every cell is created from its location, row by row
+    pub fn from_fn<F>(size: Size, mut generator: F) -> Self
+    where
+        F: FnMut((usize, usize)) -> T,
+    {
+        Self::from_cells(size, |location| Some(generator(location)))
+    }
+
+    /// Like `from_fn`, but cells can be left empty
+    pub fn from_cells<F>((width, height): Size, generator: F) -> Self
+    where
+        F: FnMut((usize, usize)) -> Option<T>,
+    {
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(generator)
+            .collect();
 
         Grid2D {
             data,
             width,
             height,
+            boundary: Boundary::default(),
+            blocked: Vec::new(),
         }
     }
 
-    pub fn get(&self, (x, y): (usize, usize)) -> Option<&T> {
-        self.data.get(y * self.width + x)
+    fn index(&self, (x, y): (usize, usize)) -> Option<usize> {
+        let i = y * self.width + x;
+        (i < self.data.len() && !is_blocked(&self.blocked, i)).then_some(i)
+    }
+
+    /// Returns None for empty and blocked cells
+    pub fn get(&self, location: (usize, usize)) -> Option<&T> {
+        self.data[self.index(location)?].as_ref()
+    }
+
+    pub fn get_mut(&mut self, location: (usize, usize)) -> Option<&mut T> {
+        let i = self.index(location)?;
+        self.data[i].as_mut()
+    }
+
+    /// The cell itself, so items can be put into or taken out of it. None for blocked cells.
+    pub fn cell_mut(&mut self, location: (usize, usize)) -> Option<&mut Option<T>> {
+        let i = self.index(location)?;
+        Some(&mut self.data[i])
     }
 
     pub fn get_pair_mut(
         &mut self,
-        (x1, y1): (usize, usize),
-        (x2, y2): (usize, usize),
+        location1: (usize, usize),
+        location2: (usize, usize),
     ) -> Option<(&mut T, &mut T)> {
-        let i1 = y1 * self.width + x1;
-        let i2 = y2 * self.width + x2;
+        let (cell1, cell2) = self.cell_pair_mut(location1, location2)?;
+        Some((cell1.as_mut()?, cell2.as_mut()?))
+    }
 
-        if i1 >= self.data.len() || i2 >= self.data.len() {
-            None
-        } else if i1 < i2 {
-            let (slice1, slice2) = self.data.split_at_mut(i2);
-            Some((&mut slice1[i1], &mut slice2[0]))
-        } else if i1 > i2 {
-            let (slice1, slice2) = self.data.split_at_mut(i1);
-            Some((&mut slice2[0], &mut slice1[i2]))
+    pub fn cell_pair_mut(
+        &mut self,
+        location1: (usize, usize),
+        location2: (usize, usize),
+    ) -> Option<(&mut Option<T>, &mut Option<T>)> {
+        let i1 = self.index(location1)?;
+        let i2 = self.index(location2)?;
+        pair_mut(&mut self.data, i1, i2)
+    }
+
+    /// Whether the cell is empty and not blocked
+    pub fn is_vacant(&self, location: (usize, usize)) -> bool {
+        self.index(location).is_some_and(|i| self.data[i].is_none())
+    }
+
+    pub fn is_blocked(&self, (x, y): (usize, usize)) -> bool {
+        is_blocked(&self.blocked, y * self.width + x)
+    }
+
+    /// The whole grid as a view
+    pub fn view_mut(&mut self) -> GridView<'_, T> {
+        GridView {
+            data: &mut self.data,
+            width: self.width,
+            height: self.height,
+            rows: 0..self.height,
+            boundary: self.boundary,
+            blocked: &self.blocked,
+        }
+    }
+
+    /// Splits the grid into views of the given row ranges, which must be sorted and disjoint
+    pub fn split_rows_mut(&mut self, ranges: &[Range<usize>]) -> Vec<GridView<'_, T>> {
+        let mut views = Vec::with_capacity(ranges.len());
+        let blocked = &self.blocked[..];
+        let mut rest = &mut self.data[..];
+        let mut rest_start = 0;
+        for rows in ranges {
+            assert!(rest_start <= rows.start && rows.start <= rows.end && rows.end <= self.height);
+            let (_, tail) =
+                std::mem::take(&mut rest).split_at_mut((rows.start - rest_start) * self.width);
+            let (data, tail) = tail.split_at_mut(rows.len() * self.width);
+            rest = tail;
+            rest_start = rows.end;
+
+            views.push(GridView {
+                data,
+                width: self.width,
+                height: self.height,
+                rows: rows.clone(),
+                boundary: self.boundary,
+                blocked,
+            });
+        }
+        views
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    /// Marks cells as impassable, indexed row by row like the grid. An empty list removes the
+    /// terrain.
+    pub fn set_terrain(&mut self, blocked: Vec<bool>) {
+        assert!(blocked.is_empty() || blocked.len() == self.data.len());
+        self.blocked = blocked;
+    }
+
+    pub fn terrain(&self) -> &[bool] {
+        &self.blocked
+    }
+
+    /// Whether the cells and the terrain both cover exactly width times height cells, which can
+    /// only be wrong for a grid read from a file
+    pub fn has_valid_size(&self) -> bool {
+        let cells = self.width.checked_mul(self.height);
+        cells == Some(self.data.len())
+            && (self.blocked.is_empty() || cells == Some(self.blocked.len()))
+    }
+
+    pub fn has_terrain(&self) -> bool {
+        !self.blocked.is_empty()
+    }
+
+    /// Every item in a cell that is not blocked
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !is_blocked(&self.blocked, *i))
+            .filter_map(|(_, cell)| cell.as_ref())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        let blocked = &self.blocked;
+        self.data
+            .iter_mut()
+            .enumerate()
+            .filter(move |(i, _)| !is_blocked(blocked, *i))
+            .filter_map(|(_, cell)| cell.as_mut())
+    }
+}
+
+/// Mutable access to some rows of a `Grid2D`, addressed with the coordinates of the whole grid
+pub struct GridView<'a, T> {
+    data: &'a mut [Option<T>],
+    width: usize,
+    height: usize,
+    rows: Range<usize>,
+    boundary: Boundary,
+    /// Terrain of the whole grid
+    blocked: &'a [bool],
+}
+
+impl<'a, T> GridView<'a, T> {
+    fn index(&self, (x, y): (usize, usize)) -> Option<usize> {
+        if x < self.width && self.rows.contains(&y) && !is_blocked(self.blocked, y * self.width + x)
+        {
+            Some((y - self.rows.start) * self.width + x)
         } else {
-            // Cannot return two aliasing mutable references
             None
         }
     }
 
-    pub fn size(&self) -> (usize, usize) {
+    /// Returns None for locations outside of the view, empty and blocked cells
+    pub fn get(&self, location: (usize, usize)) -> Option<&T> {
+        self.data[self.index(location)?].as_ref()
+    }
+
+    /// The cell itself, None for locations outside of the view and blocked cells
+    pub fn cell_mut(&mut self, location: (usize, usize)) -> Option<&mut Option<T>> {
+        let i = self.index(location)?;
+        Some(&mut self.data[i])
+    }
+
+    pub fn get_pair_mut(
+        &mut self,
+        location1: (usize, usize),
+        location2: (usize, usize),
+    ) -> Option<(&mut T, &mut T)> {
+        let (cell1, cell2) = self.cell_pair_mut(location1, location2)?;
+        Some((cell1.as_mut()?, cell2.as_mut()?))
+    }
+
+    pub fn cell_pair_mut(
+        &mut self,
+        location1: (usize, usize),
+        location2: (usize, usize),
+    ) -> Option<(&mut Option<T>, &mut Option<T>)> {
+        let i1 = self.index(location1)?;
+        let i2 = self.index(location2)?;
+        pair_mut(self.data, i1, i2)
+    }
+
+    /// Whether the cell is in the view, empty and not blocked
+    pub fn is_vacant(&self, location: (usize, usize)) -> bool {
+        self.index(location).is_some_and(|i| self.data[i].is_none())
+    }
+
+    /// Size of the whole grid
+    pub fn size(&self) -> Size {
         (self.width, self.height)
     }
 
-    pub fn count(&self) -> usize {
-        self.data.len()
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn has_terrain(&self) -> bool {
+        !self.blocked.is_empty()
+    }
+}
+
+fn is_blocked(blocked: &[bool], i: usize) -> bool {
+    blocked.get(i).copied().unwrap_or(false)
+}
+
+fn pair_mut<T>(data: &mut [T], i1: usize, i2: usize) -> Option<(&mut T, &mut T)> {
+    if i1 >= data.len() || i2 >= data.len() {
+        None
+    } else if i1 < i2 {
+        let (slice1, slice2) = data.split_at_mut(i2);
+        Some((&mut slice1[i1], &mut slice2[0]))
+    } else if i1 > i2 {
+        let (slice1, slice2) = data.split_at_mut(i1);
+        Some((&mut slice2[0], &mut slice1[i2]))
+    } else {
+        // Cannot return two aliasing mutable references
+        None
     }
 }
 
@@ -74,4 +368,56 @@ mod tests {
         assert_eq!(grid.get((0, 0)), Some(&2));
         assert_eq!(grid.get((7, 1)), Some(&3));
     }
+
+    #[test]
+    fn test_empty_cells() {
+        let mut grid = Grid2D::from_cells((4, 2), |(x, _)| (x > 0).then_some(x));
+        assert_eq!(grid.get((0, 1)), None);
+        assert!(grid.is_vacant((0, 1)));
+        assert!(!grid.is_vacant((1, 1)));
+        assert!(grid.get_pair_mut((0, 1), (1, 1)).is_none());
+        assert_eq!(grid.iter().count(), 6);
+
+        *grid.cell_mut((0, 1)).unwrap() = Some(7);
+        assert_eq!(grid.get((0, 1)), Some(&7));
+        grid.set_terrain(vec![false, false, false, false, true, false, false, false]);
+        assert!(grid.cell_mut((0, 1)).is_none());
+        assert!(!grid.is_vacant((0, 1)));
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let grid = Grid2D::from_fn((3, 2), |location| location);
+        assert_eq!(grid.get((2, 1)), Some(&(2, 1)));
+        assert_eq!(grid.get((0, 1)), Some(&(0, 1)));
+    }
+
+    #[test]
+    fn test_split_rows() {
+        let mut grid = Grid2D::new_with((4, 6), || 0);
+        let mut views = grid.split_rows_mut(&[0..2, 3..6]);
+        assert_eq!(views[0].get((0, 2)), None);
+        assert_eq!(views[1].get((0, 2)), None);
+        assert_eq!(views[1].size(), (4, 6));
+
+        if let Some(pair) = views[1].get_pair_mut((3, 3), (0, 5)) {
+            *pair.0 = 1;
+            *pair.1 = 2;
+        }
+        assert!(views[0].get_pair_mut((0, 0), (0, 3)).is_none());
+        assert_eq!(grid.get((3, 3)), Some(&1));
+        assert_eq!(grid.get((0, 5)), Some(&2));
+    }
+
+    #[test]
+    fn test_boundary() {
+        let size = (10, 5);
+        assert_eq!(Boundary::Torus.apply((0, 4), (-1, 1), size), Some((9, 0)));
+        assert_eq!(Boundary::Walls.apply((0, 2), (-1, 0), size), None);
+        assert_eq!(Boundary::Walls.apply((1, 2), (-1, 2), size), Some((0, 4)));
+        assert_eq!(Boundary::Reflect.apply((0, 4), (-1, 2), size), Some((1, 2)));
+        assert_eq!(Boundary::Reflect.apply((0, 0), (-20, 0), size), None);
+        assert_eq!(Boundary::Cylinder.apply((9, 2), (1, 0), size), Some((0, 2)));
+        assert_eq!(Boundary::Cylinder.apply((9, 0), (0, -1), size), None);
+    }
 }
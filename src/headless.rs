@@ -0,0 +1,70 @@
+use crate::args::{Args, EndAction, FighterType};
+use crate::battle::Fighter;
+use crate::recording::Recorder;
+use crate::render::render;
+use crate::snapshot::save_snapshot;
+use crate::types::{Colored, GenerateRandomly, Typed};
+use image::RgbImage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+
+/// Runs the battle described by `args` without opening a window, then writes the final grid to
+/// `args.output`.
+pub fn run_headless<T>(args: &Args, fighter_type: &FighterType) -> io::Result<()>
+where
+    T: Colored + Fighter + GenerateRandomly + Typed + Clone + Send + Serialize + DeserializeOwned,
+{
+    let mut battle = args.create_battle::<T>(fighter_type)?;
+    let (width, height) = battle.size();
+    let mut image = RgbImage::new(width as u32, height as u32);
+    let mut recorder = Recorder::new(args)?;
+    if recorder.is_due(battle.round()) {
+        render(&battle, &mut image, args.render_mode);
+        recorder.record(&image)?;
+    }
+
+    let mut rounds = 0;
+    let mut ended = false;
+    while rounds < args.rounds {
+        battle.action();
+        rounds += 1;
+        if recorder.is_due(battle.round()) {
+            render(&battle, &mut image, args.render_mode);
+            recorder.record(&image)?;
+        }
+
+        match battle.outcome(args.stalemate_rounds) {
+            Some(outcome) if !ended => {
+                println!("{}", battle.stats().describe_outcome(outcome));
+                match args.on_end {
+                    EndAction::Continue => ended = true,
+                    EndAction::Pause => break,
+                    EndAction::Exit => {
+                        println!("{}", battle.stats().summary());
+                        break;
+                    }
+                    EndAction::Restart => battle.regenerate(),
+                }
+            }
+            Some(_) => {}
+            None => ended = false,
+        }
+    }
+
+    render(&battle, &mut image, args.render_mode);
+    image.save(&args.output).map_err(io::Error::other)?;
+    if let Some(stats) = &args.stats {
+        battle.stats().save(stats)?;
+    }
+    if let Some(snapshot) = &args.snapshot {
+        save_snapshot(&battle, fighter_type, snapshot)?;
+    }
+
+    println!(
+        "Simulated {} rounds, result written to {}",
+        rounds,
+        args.output.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,369 @@
+// Patterns the fighters of a new grid can start in.
+
+use crate::args::validate_dimensions;
+use crate::grid::{Grid2D, Size};
+use crate::types::{Colored, GenerateRandomly, Initializer, Typed};
+use clap::ValueEnum;
+use image::imageops::{self, FilterType};
+use image::{GrayImage, RgbImage};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::index::sample;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutKind {
+    /// Every cell picks its type at random
+    #[default]
+    Random,
+    VerticalStripes,
+    HorizontalStripes,
+    /// Clustered blobs around random points, one type per blob
+    Blobs,
+    /// Concentric rings around the center of the grid
+    Rings,
+    Checkerboard,
+    /// A single fighter of every type, or as many as its weight, in an empty grid. Needs
+    /// reproduction to spread.
+    Seeds,
+}
+
+/// How a new grid is filled. Patterns repeat the types in order, with every type taking as many
+/// stripes, rings or squares as its weight.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Layout {
+    pub kind: LayoutKind,
+    /// Width of stripes, rings and squares and the average size of blobs
+    pub scale: usize,
+    /// Relative share of every type, an empty list gives every type the same share
+    pub weights: Vec<u32>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            kind: LayoutKind::default(),
+            scale: 16,
+            weights: Vec::new(),
+        }
+    }
+}
+
+impl Layout {
+    /// Checks the weights against the number of fighter types
+    pub fn validate(&self, type_count: usize) -> io::Result<()> {
+        if self.weights.is_empty() {
+            return Ok(());
+        }
+
+        if self.weights.len() != type_count {
+            return Err(invalid_input(format!(
+                "expected {type_count} weights, one for every type, but got {}",
+                self.weights.len()
+            )));
+        }
+        if self.weights.iter().all(|&weight| weight == 0) {
+            return Err(invalid_input(
+                "at least one weight should be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn weights(&self, type_count: usize) -> Vec<u32> {
+        if self.weights.is_empty() {
+            vec![1; type_count]
+        } else {
+            self.weights.clone()
+        }
+    }
+
+    fn distribution(&self, type_count: usize) -> WeightedIndex<u32> {
+        WeightedIndex::new(self.weights(type_count)).expect("layout weights should be validated")
+    }
+
+    /// Every type repeated as often as its weight
+    fn sequence(&self, type_count: usize) -> Vec<usize> {
+        self.weights(type_count)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(kind, weight)| std::iter::repeat_n(kind, weight as usize))
+            .collect()
+    }
+
+    /// Position of a cell in the repeating sequence of types
+    fn pattern_index(&self, (x, y): (usize, usize), (width, height): Size) -> usize {
+        let scale = self.scale.max(1);
+        match self.kind {
+            LayoutKind::VerticalStripes => x / scale,
+            LayoutKind::HorizontalStripes => y / scale,
+            LayoutKind::Checkerboard => x / scale + y / scale,
+            LayoutKind::Rings => {
+                let dx = x as f64 - width as f64 / 2.0;
+                let dy = y as f64 - height as f64 / 2.0;
+                (dx.hypot(dy) / scale as f64) as usize
+            }
+            LayoutKind::Random | LayoutKind::Blobs | LayoutKind::Seeds => 0,
+        }
+    }
+
+    fn blobs<T, R>(&self, (width, height): Size, rng: &mut R) -> Grid2D<T>
+    where
+        T: Typed,
+        R: Rng,
+    {
+        // One blob center at a random spot of every scale x scale block. The center in a cell's own
+        // block is less than 1.5 scale away and centers three blocks away are further than 2 scale,
+        // so the closest center is always in one of the 25 blocks around a cell
+        let scale = self.scale.max(1);
+        let blocks = (width.div_ceil(scale), height.div_ceil(scale));
+        let distribution = self.distribution(T::type_names().len());
+        let centers = Grid2D::from_fn(blocks, |(bx, by)| {
+            let x = bx * scale + rng.gen_range(0..scale);
+            let y = by * scale + rng.gen_range(0..scale);
+            ((x, y), distribution.sample(rng))
+        });
+
+        Grid2D::from_fn((width, height), |(x, y)| {
+            let (bx, by) = (x / scale, y / scale);
+            let neighbours = (by.saturating_sub(2)..=by + 2)
+                .flat_map(|by| (bx.saturating_sub(2)..=bx + 2).map(move |bx| (bx, by)));
+            let kind = neighbours
+                .filter(|&(bx, _)| bx < blocks.0)
+                .filter_map(|block| centers.get(block))
+                .min_by_key(|((cx, cy), _)| cx.abs_diff(x).pow(2) + cy.abs_diff(y).pow(2))
+                .map(|&(_, kind)| kind)
+                .unwrap_or_default();
+            T::from_type_index(kind)
+        })
+    }
+}
+
+impl<T> Initializer<T> for Layout
+where
+    T: GenerateRandomly + Typed,
+{
+    fn populate<R>(&self, size: Size, rng: &mut R) -> Grid2D<T>
+    where
+        R: Rng,
+    {
+        let type_count = T::type_names().len();
+        match self.kind {
+            LayoutKind::Random if self.weights.is_empty() => {
+                Grid2D::new_with(size, || T::generate_randomly(rng))
+            }
+            LayoutKind::Random => {
+                let distribution = self.distribution(type_count);
+                Grid2D::new_with(size, || T::from_type_index(distribution.sample(rng)))
+            }
+            LayoutKind::Blobs => self.blobs(size, rng),
+            LayoutKind::Seeds => {
+                let sequence = self.sequence(type_count);
+                let (width, height) = size;
+                let cells = width * height;
+                let locations = sample(rng, cells, sequence.len().min(cells));
+                let mut grid = Grid2D::from_cells(size, |_| None);
+                for (i, kind) in locations.iter().zip(sequence) {
+                    if let Some(cell) = grid.cell_mut((i % width, i / width)) {
+                        *cell = Some(T::from_type_index(kind));
+                    }
+                }
+                grid
+            }
+            _ => {
+                let sequence = self.sequence(type_count);
+                Grid2D::from_fn(size, |location| {
+                    let index = self.pattern_index(location, size) % sequence.len();
+                    T::from_type_index(sequence[index])
+                })
+            }
+        }
+    }
+}
+
+/// Starts a battle from an image, every pixel becomes a fighter of the type with the closest color
+pub struct ImageLayout {
+    image: RgbImage,
+}
+
+impl ImageLayout {
+    /// Opens an image whose width and height are within the supported grid sizes
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let image = image::open(path).map_err(io::Error::other)?.into_rgb8();
+        Self::new(image)
+    }
+
+    pub fn new(image: RgbImage) -> io::Result<Self> {
+        let (width, height) = image.dimensions();
+        validate_dimensions(width as usize, height as usize)
+            .map_err(|err| invalid_input(format!("image {err}")))?;
+        Ok(ImageLayout { image })
+    }
+
+    pub fn size(&self) -> Size {
+        let (width, height) = self.image.dimensions();
+        (width as usize, height as usize)
+    }
+}
+
+impl<T> Initializer<T> for ImageLayout
+where
+    T: Colored + Typed,
+{
+    /// Fills the part of the grid covered by the image, `size` should be the size of the image
+    fn populate<R>(&self, size: Size, _rng: &mut R) -> Grid2D<T>
+    where
+        R: Rng,
+    {
+        let palette: Vec<_> = (0..T::type_names().len())
+            .map(|kind| T::from_type_index(kind).color())
+            .collect();
+        let distance = |a: [u8; 3], b: [u8; 3]| {
+            a.iter()
+                .zip(b)
+                .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+
+        Grid2D::from_fn(size, |(x, y)| {
+            let color = self.image.get_pixel(x as u32, y as u32).0;
+            let closest = (0..palette.len())
+                .min_by_key(|&kind| distance(palette[kind], color))
+                .unwrap_or_default();
+            T::from_color(color, closest)
+        })
+    }
+}
+
+/// Reads the walls of a black and white terrain image scaled to `size`, dark pixels are walls
+pub fn load_terrain(path: &Path, size: Size) -> io::Result<Vec<bool>> {
+    let image = image::open(path).map_err(io::Error::other)?.into_luma8();
+    Ok(terrain_from_image(&image, size))
+}
+
+fn terrain_from_image(image: &GrayImage, (width, height): Size) -> Vec<bool> {
+    let image = imageops::resize(image, width as u32, height as u32, FilterType::Nearest);
+    image.pixels().map(|pixel| pixel.0[0] < 128).collect()
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_fight::ColorFighter;
+    use crate::rps::RPS;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn populate(layout: &Layout, size: Size) -> Grid2D<RPS> {
+        layout.populate(size, &mut ChaCha8Rng::seed_from_u64(1))
+    }
+
+    fn counts(grid: &Grid2D<RPS>) -> [usize; 3] {
+        let mut counts = [0; 3];
+        grid.iter()
+            .for_each(|fighter| counts[fighter.type_index()] += 1);
+        counts
+    }
+
+    #[test]
+    fn test_patterns() {
+        let layout = |kind| Layout {
+            kind,
+            scale: 2,
+            weights: vec![1, 2, 0],
+        };
+
+        let stripes = populate(&layout(LayoutKind::VerticalStripes), (6, 4));
+        let types: Vec<_> = (0..6)
+            .map(|x| stripes.get((x, 3)).unwrap().type_index())
+            .collect();
+        assert_eq!(types, [0, 0, 1, 1, 1, 1]);
+        assert_eq!(counts(&stripes), [8, 16, 0]);
+
+        let checkerboard = populate(&layout(LayoutKind::Checkerboard), (6, 6));
+        assert_eq!(checkerboard.get((0, 0)).unwrap().type_index(), 0);
+        assert_eq!(checkerboard.get((2, 0)).unwrap().type_index(), 1);
+        assert_eq!(checkerboard.get((2, 4)).unwrap().type_index(), 0);
+
+        let rings = populate(&layout(LayoutKind::Rings), (8, 8));
+        assert_eq!(rings.get((4, 4)).unwrap().type_index(), 0);
+        assert_eq!(rings.get((4, 7)).unwrap().type_index(), 1);
+
+        let seeds = populate(&layout(LayoutKind::Seeds), (8, 8));
+        assert_eq!(counts(&seeds), [1, 2, 0]);
+    }
+
+    #[test]
+    fn test_weights() {
+        let layout = Layout {
+            kind: LayoutKind::Random,
+            weights: vec![3, 1, 0],
+            ..Layout::default()
+        };
+        let random = counts(&populate(&layout, (64, 64)));
+        assert_eq!(random[2], 0);
+        assert!(random[0] > 2 * random[1]);
+
+        let blobs = Layout {
+            kind: LayoutKind::Blobs,
+            ..layout.clone()
+        };
+        let grid = populate(&blobs, (64, 64));
+        assert_eq!(counts(&grid)[2], 0);
+        // Neighbours mostly share a blob
+        let same = (1..64)
+            .filter(|&x| {
+                grid.get((x, 10)).unwrap().type_index()
+                    == grid.get((x - 1, 10)).unwrap().type_index()
+            })
+            .count();
+        assert!(same > 48);
+
+        assert!(layout.validate(3).is_ok());
+        assert!(layout.validate(4).is_err());
+        let zero = Layout {
+            weights: vec![0, 0, 0],
+            ..layout
+        };
+        assert!(zero.validate(3).is_err());
+    }
+
+    #[test]
+    fn test_terrain() {
+        let mut image = GrayImage::from_pixel(2, 2, image::Luma([255]));
+        image.put_pixel(1, 0, image::Luma([0]));
+        let terrain = terrain_from_image(&image, (4, 2));
+        assert_eq!(
+            terrain,
+            [false, false, true, true, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn test_image() {
+        assert!(ImageLayout::new(RgbImage::new(16, 32)).is_err());
+
+        let mut image = RgbImage::new(32, 32);
+        image.put_pixel(1, 0, image::Rgb([250, 10, 10]));
+        image.put_pixel(2, 0, image::Rgb([20, 30, 240]));
+        let layout = ImageLayout::new(image).unwrap();
+        assert_eq!(layout.size(), (32, 32));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let grid: Grid2D<RPS> = layout.populate(layout.size(), &mut rng);
+        let color = |x: usize| grid.get((x, 0)).unwrap().color();
+        assert_eq!(color(1), RPS::from_type_index(0).color());
+        assert_eq!(color(2), RPS::from_type_index(1).color());
+
+        let grid: Grid2D<ColorFighter> = layout.populate(layout.size(), &mut rng);
+        assert_eq!(grid.get((2, 0)).unwrap().color(), [20, 30, 240]);
+    }
+}
@@ -1,16 +1,43 @@
 mod args;
 mod battle;
+mod brush;
 mod color_fight;
+mod experience;
 mod grid;
+mod headless;
+mod layout;
+mod neighbourhood;
 mod pokemon;
+mod recording;
+mod render;
 mod rps;
+mod simulation;
+mod snapshot;
+mod stats;
 mod street_fighter;
+mod sweep;
+mod table_fighter;
 mod types;
+mod vitality;
 
-pub use args::{Args, FighterType};
-pub use battle::{Battle, Fighter, SelectionAlgorithm};
+pub use args::{Args, EndAction, FighterType, GRID_SIZES};
+pub use battle::{Battle, Fighter, SelectionAlgorithm, UpdateMode};
+pub use brush::{Brush, BrushShape};
 pub use color_fight::ColorFighter;
+pub use experience::Experience;
+pub use grid::Boundary;
+pub use headless::run_headless;
+pub use layout::{load_terrain, ImageLayout, Layout, LayoutKind};
+pub use neighbourhood::{Neighbourhood, NeighbourhoodKind};
 pub use pokemon::Pokemon;
+pub use recording::Recorder;
+pub use render::{render, RenderMode};
 pub use rps::RPS;
+pub use simulation::{create_simulation, Simulation};
+pub use snapshot::{load_snapshot, save_snapshot, snapshot_fighter_type};
+pub use stats::{Outcome, RoundStats, Statistics};
 pub use street_fighter::StreetFighter;
-pub use types::{Colored, GenerateRandomly};
+pub use sweep::{run_once, run_sweep, run_sweep_file, write_csv, SweepResult, SweepSpec};
+pub use table_fighter::{FighterTable, TableFighter};
+pub use types::{Colored, FighterOptions, GenerateRandomly, Initializer, Typed};
+pub use vitality::Vitality;
@@ -1,20 +1,22 @@
-use clap::Parser;
-use core::fmt::Display;
+use clap::{Parser, ValueEnum};
 use nannou::image::GenericImageView;
 use nannou::prelude::{App, Frame, Update};
 use nannou_egui::{egui, Egui};
 use once_cell::sync::OnceCell;
 use poke_fighting_rust::{
-    Args, Battle, ColorFighter, Colored, Fighter, FighterType, GenerateRandomly, Pokemon,
-    SelectionAlgorithm, StreetFighter, RPS,
+    create_simulation, run_headless, run_sweep_file, Args, Brush, BrushShape, ColorFighter,
+    EndAction, FighterType, LayoutKind, Outcome, Pokemon, Recorder, RenderMode, SelectionAlgorithm,
+    Simulation, StreetFighter, TableFighter, GRID_SIZES, RPS,
 };
 use std::cmp::min;
+use std::fmt;
 use std::fs::File;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // Needed because of nannou's not so great model function pointer
 static ARGS: OnceCell<Args> = OnceCell::new();
+static FIGHTER_TYPE: OnceCell<FighterType> = OnceCell::new();
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -24,9 +26,15 @@ pub struct AllArgs {
     /// Config JSON file to use. When passed, overrides all command line arguments
     #[clap(short = 'c', long)]
     config: Option<PathBuf>,
+    /// Run the simulation without a window and write the result to disk
+    #[clap(long)]
+    headless: bool,
+    /// Sweep JSON file, runs every combination of its settings headless and writes a summary
+    #[clap(long)]
+    sweep: Option<PathBuf>,
 }
 
-fn parse_args() -> io::Result<&'static Args> {
+fn parse_args() -> io::Result<(&'static Args, bool, Option<PathBuf>)> {
     let all_args = AllArgs::parse();
     let args = if let Some(config) = all_args.config {
         let file = File::open(config)?;
@@ -35,46 +43,206 @@ fn parse_args() -> io::Result<&'static Args> {
     } else {
         all_args.args
     };
-    Ok(ARGS.get_or_init(|| args))
+    Ok((ARGS.get_or_init(|| args), all_args.headless, all_args.sweep))
 }
 
 fn main() -> io::Result<()> {
-    let args = parse_args()?;
-    match args.fighter_type {
-        FighterType::Pokemon => run_app::<Pokemon>(),
-        FighterType::RockPaperScissors => run_app::<RPS>(),
-        FighterType::StreetFighter => run_app::<StreetFighter>(),
-        FighterType::ColorFighter => run_app::<ColorFighter>(),
-    };
+    let (args, headless, sweep) = parse_args()?;
+    let fighter_type = args.resolve_fighter_type()?;
+    let fighter_type = FIGHTER_TYPE.get_or_init(|| fighter_type);
+    // The table is loaded even for other fighter types, so the control panel can switch to it
+    if let Some(table) = &args.table {
+        TableFighter::load_table(table)?;
+    } else if let FighterType::Custom = fighter_type {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the custom fighter type needs a --table file",
+        ));
+    }
+
+    if let Some(sweep) = sweep {
+        return run_sweep_file(&sweep, args);
+    }
+
+    if headless {
+        return match fighter_type {
+            FighterType::Pokemon => run_headless::<Pokemon>(args, fighter_type),
+            FighterType::RockPaperScissors => run_headless::<RPS>(args, fighter_type),
+            FighterType::StreetFighter => run_headless::<StreetFighter>(args, fighter_type),
+            FighterType::ColorFighter => run_headless::<ColorFighter>(args, fighter_type),
+            FighterType::Custom => run_headless::<TableFighter>(args, fighter_type),
+        };
+    }
+
+    nannou::app(model).update(update).exit(exit).run();
     Ok(())
 }
 
-fn run_app<T>()
-where
-    T: 'static + Colored + Fighter + GenerateRandomly + Display,
-{
-    nannou::app(model::<T>).update(update).exit(exit).run()
+/// How fast the simulation runs in the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Speed {
+    RoundsPerFrame(usize),
+    /// Slow motion, one round every few frames
+    FramesPerRound(usize),
+}
+
+impl Speed {
+    const MAX_ROUNDS_PER_FRAME: usize = 1024;
+    const MAX_FRAMES_PER_ROUND: usize = 64;
+
+    fn faster(self) -> Self {
+        match self {
+            Speed::FramesPerRound(frames) if frames > 2 => Speed::FramesPerRound(frames / 2),
+            Speed::FramesPerRound(_) => Speed::RoundsPerFrame(1),
+            Speed::RoundsPerFrame(rounds) => {
+                Speed::RoundsPerFrame((rounds * 2).min(Self::MAX_ROUNDS_PER_FRAME))
+            }
+        }
+    }
+
+    fn slower(self) -> Self {
+        match self {
+            Speed::RoundsPerFrame(rounds) if rounds > 1 => Speed::RoundsPerFrame(rounds / 2),
+            Speed::RoundsPerFrame(_) => Speed::FramesPerRound(2),
+            Speed::FramesPerRound(frames) => {
+                Speed::FramesPerRound((frames * 2).min(Self::MAX_FRAMES_PER_ROUND))
+            }
+        }
+    }
+
+    /// Number of rounds to run in the given frame
+    fn rounds(self, frame: u64) -> usize {
+        match self {
+            Speed::RoundsPerFrame(rounds) => rounds,
+            Speed::FramesPerRound(frames) if frame.is_multiple_of(frames as u64) => 1,
+            Speed::FramesPerRound(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Speed::RoundsPerFrame(1) => write!(f, "1 round per frame"),
+            Speed::RoundsPerFrame(rounds) => write!(f, "{rounds} rounds per frame"),
+            Speed::FramesPerRound(frames) => write!(f, "1 round every {frames} frames"),
+        }
+    }
+}
+
+/// Zoom and pan of the grid in the window
+#[derive(Clone, Copy, Debug)]
+struct Camera {
+    /// 1 fits the whole grid into the window
+    zoom: f32,
+    /// Point of the image shown in the middle of the window, relative to the middle of the image
+    /// with y pointing up like window coordinates
+    center: (f32, f32),
+}
+
+impl Camera {
+    const MAX_ZOOM: f32 = 64.0;
+    /// Zoom factor of a single mouse wheel step
+    const ZOOM_STEP: f32 = 1.25;
+
+    fn new() -> Self {
+        Camera {
+            zoom: 1.0,
+            center: (0.0, 0.0),
+        }
+    }
+
+    /// Size of an image pixel in the window
+    fn scale(&self, window_size: (u32, u32), image_size: (u32, u32)) -> f32 {
+        get_image_ratio(window_size, image_size) * self.zoom
+    }
+
+    /// Converts window coordinates into image coordinates relative to the middle of the image
+    fn window_to_image(
+        &self,
+        (x, y): (f32, f32),
+        window_size: (u32, u32),
+        image_size: (u32, u32),
+    ) -> (f32, f32) {
+        let scale = self.scale(window_size, image_size);
+        (x / scale + self.center.0, y / scale + self.center.1)
+    }
+
+    /// Zooms by `steps` mouse wheel steps while keeping the image point under `position` in place
+    fn zoom_at(
+        &mut self,
+        position: (f32, f32),
+        steps: f32,
+        window_size: (u32, u32),
+        image_size: (u32, u32),
+    ) {
+        let before = self.window_to_image(position, window_size, image_size);
+        self.zoom = (self.zoom * Self::ZOOM_STEP.powf(steps)).clamp(1.0, Self::MAX_ZOOM);
+        let after = self.window_to_image(position, window_size, image_size);
+        self.center.0 += before.0 - after.0;
+        self.center.1 += before.1 - after.1;
+        self.clamp(image_size);
+    }
+
+    /// Moves the image along with the mouse
+    fn pan(&mut self, (dx, dy): (f32, f32), window_size: (u32, u32), image_size: (u32, u32)) {
+        let scale = self.scale(window_size, image_size);
+        self.center.0 -= dx / scale;
+        self.center.1 -= dy / scale;
+        self.clamp(image_size);
+    }
+
+    /// Keeps the middle of the window on the image
+    fn clamp(&mut self, (width, height): (u32, u32)) {
+        let (half_width, half_height) = (width as f32 * 0.5, height as f32 * 0.5);
+        self.center.0 = self.center.0.clamp(-half_width, half_width);
+        self.center.1 = self.center.1.clamp(-half_height, half_height);
+    }
 }
 
-struct Model<T> {
-    battle: Battle<T>,
+struct Model {
+    simulation: Box<dyn Simulation>,
+    fighter_type: FighterType,
+    /// Used when the grid is reset from the control panel
+    settings: Args,
     image: nannou::image::DynamicImage,
     window_size: (u32, u32),
+    camera: Camera,
+    /// Mouse wheel steps since the last update
+    scroll: f32,
+    last_mouse: (f32, f32),
     paused: bool,
+    speed: Speed,
+    /// How the battle ended, if it has
+    outcome: Option<Outcome>,
     display_framerate: bool,
     info: Egui,
     info_visible: bool,
+    controls_visible: bool,
+    palette_visible: bool,
+    chart_visible: bool,
+    chart_stacked: bool,
+    paint_type: usize,
+    brush: Brush,
+    recorder: Option<Recorder>,
+    /// Number of recordings started so far, later ones are numbered so they don't overwrite
+    /// earlier ones
+    recordings: usize,
 }
 
-fn model<T: 'static + Fighter + GenerateRandomly>(app: &App) -> Model<T> {
+fn model(app: &App) -> Model {
     let args = ARGS.get().unwrap();
-    let img_width = args.width;
-    let img_height = args.height;
-    let selection_algorithm = if args.random {
-        SelectionAlgorithm::RandomNeighbour
-    } else {
-        SelectionAlgorithm::WeakestNeighbour
-    };
+    let fighter_type = FIGHTER_TYPE.get().unwrap().clone();
+    let simulation = create_simulation(args, &fighter_type).expect("could not create the battle");
+    let (img_width, img_height) = simulation.size();
+
+    // Resetting starts a new grid instead of loading the snapshot again
+    let mut settings = args.clone();
+    settings.load_snapshot = None;
+    settings.image = None;
+    settings.fighter_type = fighter_type.clone();
+    settings.width = img_width;
+    settings.height = img_height;
 
     let surface_conf_builder = nannou::window::SurfaceConfigurationBuilder::new()
         .present_mode(nannou::wgpu::PresentMode::Mailbox);
@@ -82,74 +250,438 @@ fn model<T: 'static + Fighter + GenerateRandomly>(app: &App) -> Model<T> {
         .new_window()
         .size(img_width as u32, img_height as u32)
         .surface_conf_builder(surface_conf_builder)
-        .view(view::<T>)
-        .key_pressed(key_pressed::<T>)
-        .resized(resized::<T>)
-        .mouse_pressed(mouse_pressed::<T>)
-        .raw_event(raw_event::<T>)
+        .view(view)
+        .key_pressed(key_pressed)
+        .resized(resized)
+        .mouse_pressed(mouse_pressed)
+        .mouse_wheel(mouse_wheel)
+        .raw_event(raw_event)
         .build()
         .unwrap();
 
     let window = app.window(window_id).unwrap();
 
     Model {
-        battle: Battle::new(img_width, img_height, selection_algorithm, !args.fightown),
+        simulation,
+        fighter_type,
+        settings,
         image: nannou::image::DynamicImage::ImageRgb8(nannou::image::RgbImage::new(
             img_width as u32,
             img_height as u32,
         )),
         window_size: (img_width as u32, img_height as u32),
+        camera: Camera::new(),
+        scroll: 0.0,
+        last_mouse: (0.0, 0.0),
         paused: false,
+        speed: Speed::RoundsPerFrame(args.rounds_per_frame),
+        outcome: None,
         display_framerate: args.framerate,
         info: Egui::from_window(&window),
         info_visible: false,
+        controls_visible: false,
+        palette_visible: false,
+        chart_visible: false,
+        chart_stacked: false,
+        paint_type: 0,
+        brush: Brush::default(),
+        recorder: None,
+        recordings: 0,
     }
 }
 
-fn update<T: Fighter + Colored + Display>(app: &App, model: &mut Model<T>, _update: Update) {
+fn update(app: &App, model: &mut Model, _update: Update) {
     if !model.paused {
-        model.battle.action();
-
-        if let nannou::image::DynamicImage::ImageRgb8(ref mut pixels) = model.image {
-            for (x, y, pixel) in pixels.enumerate_pixels_mut() {
-                if let Some(fighter) = model.battle.fighter((x as usize, y as usize)) {
-                    *pixel = fighter.color();
-                } else {
-                    *pixel = [0, 0, 0].into()
-                }
-            }
-        }
+        advance(app, model, model.speed.rounds(app.elapsed_frames()));
     }
 
     let window_size = model.window_size;
     let image_size = model.image.dimensions();
+    let mouse = (app.mouse.x, app.mouse.y);
+    let (x, y) = window_coords_to_image(mouse, window_size, image_size, &model.camera);
+    let mut reset = false;
+    let mut toggle_pause = false;
+    let mut step = false;
+    let mut recolor = false;
     let ctx = model.info.begin_frame();
+    egui::Area::new("status")
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .interactable(false)
+        .show(&ctx, |ui| {
+            let state = if model.paused {
+                "Paused".to_string()
+            } else {
+                model.speed.to_string()
+            };
+            ui.label(format!(
+                "Round {} | {state} | {:.0} fps",
+                model.simulation.round(),
+                app.fps()
+            ));
+            if let Some(outcome) = model.outcome {
+                ui.label(model.simulation.stats().describe_outcome(outcome));
+            }
+        });
+
     egui::Window::new("Fighter info")
         .resizable(false)
         .collapsible(false)
         .open(&mut model.info_visible)
         .show(&ctx, |ui| {
-            let (x, y) =
-                window_coords_to_image((app.mouse.x, app.mouse.y), window_size, image_size);
             ui.label(format!("Position: {x}, {y}"));
-            if let Some(fighter) = model.battle.fighter((x as usize, y as usize)) {
-                let color = fighter.color();
+            if let Some((description, color)) = model.simulation.describe((x as usize, y as usize))
+            {
                 ui.horizontal(|ui| {
-                    let size = ui.spacing().icon_width;
-                    let (rect, _response) = ui
-                        .allocate_exact_size(egui::Vec2 { x: size, y: size }, egui::Sense::hover());
-                    ui.painter().circle_filled(
-                        rect.center(),
-                        size * 0.5,
-                        egui::Color32::from_rgb(color[0], color[1], color[2]),
-                    );
-
-                    ui.label(format!("{fighter}"));
+                    color_swatch(ui, color);
+                    ui.label(description);
                 });
             } else {
                 ui.label("Nothing here");
             }
         });
+
+    if model.controls_visible {
+        egui::SidePanel::left("controls")
+            .resizable(false)
+            .show(&ctx, |ui| {
+                ui.heading("Simulation");
+                let mut selection_algorithm = model.simulation.selection_algorithm();
+                ui.radio_value(
+                    &mut selection_algorithm,
+                    SelectionAlgorithm::WeakestNeighbour,
+                    "Attack the weakest neighbour",
+                );
+                ui.radio_value(
+                    &mut selection_algorithm,
+                    SelectionAlgorithm::RandomNeighbour,
+                    "Attack a random neighbour",
+                );
+                model
+                    .simulation
+                    .set_selection_algorithm(selection_algorithm);
+
+                let mut fightown = !model.simulation.filter_fight_candidates();
+                ui.checkbox(&mut fightown, "Fight their own kind");
+                model.simulation.set_filter_fight_candidates(!fightown);
+
+                if model.fighter_type == FighterType::Pokemon {
+                    let mut options = model.simulation.options();
+                    ui.checkbox(&mut options.dual_types, "Dual types");
+                    ui.checkbox(&mut options.base_stats, "Base stats");
+                    model.simulation.set_options(options);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Slower").clicked() {
+                        model.speed = model.speed.slower();
+                    }
+                    if ui.button("Faster").clicked() {
+                        model.speed = model.speed.faster();
+                    }
+                    ui.label(model.speed.to_string());
+                });
+                ui.label("When the battle ends");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut model.settings.on_end, EndAction::Continue, "Continue");
+                    ui.radio_value(&mut model.settings.on_end, EndAction::Pause, "Pause");
+                    ui.radio_value(&mut model.settings.on_end, EndAction::Exit, "Exit");
+                    ui.radio_value(&mut model.settings.on_end, EndAction::Restart, "Restart");
+                });
+                ui.label("Colors show");
+                ui.horizontal(|ui| {
+                    let render_mode = &mut model.settings.render_mode;
+                    recolor |= ui
+                        .radio_value(render_mode, RenderMode::Type, "Type")
+                        .changed();
+                    recolor |= ui
+                        .radio_value(render_mode, RenderMode::Health, "Health")
+                        .changed();
+                    recolor |= ui
+                        .radio_value(render_mode, RenderMode::Level, "Level")
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    toggle_pause = ui
+                        .button(if model.paused { "Resume" } else { "Pause" })
+                        .clicked();
+                    step = ui
+                        .add_enabled(model.paused, egui::Button::new("Step"))
+                        .clicked();
+                });
+
+                ui.separator();
+                ui.heading("New grid");
+                egui::ComboBox::from_label("Fighter type")
+                    .selected_text(format!("{:?}", model.settings.fighter_type))
+                    .show_ui(ui, |ui| {
+                        for fighter_type in FighterType::value_variants() {
+                            ui.selectable_value(
+                                &mut model.settings.fighter_type,
+                                fighter_type.clone(),
+                                format!("{fighter_type:?}"),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_label("Layout")
+                    .selected_text(format!("{:?}", model.settings.layout))
+                    .show_ui(ui, |ui| {
+                        for layout in LayoutKind::value_variants() {
+                            ui.selectable_value(
+                                &mut model.settings.layout,
+                                *layout,
+                                format!("{layout:?}"),
+                            );
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut model.settings.width, GRID_SIZES).text("Width"));
+                ui.add(egui::Slider::new(&mut model.settings.height, GRID_SIZES).text("Height"));
+                reset = ui.button("Reset").clicked();
+            });
+    }
+
+    egui::Window::new("Paint")
+        .resizable(false)
+        .collapsible(false)
+        .open(&mut model.palette_visible)
+        .show(&ctx, |ui| {
+            let names = model.simulation.stats().types();
+            ui.horizontal(|ui| {
+                color_swatch(ui, model.simulation.type_color(model.paint_type));
+                egui::ComboBox::from_label("Type")
+                    .selected_text(&names[model.paint_type])
+                    .show_ui(ui, |ui| {
+                        for (i, name) in names.iter().enumerate() {
+                            ui.selectable_value(&mut model.paint_type, i, name);
+                        }
+                    });
+            });
+            ui.add(egui::Slider::new(&mut model.brush.radius, 0..=32).text("Brush radius"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.brush.shape, BrushShape::Circle, "Circle");
+                ui.radio_value(&mut model.brush.shape, BrushShape::Square, "Square");
+            });
+            ui.label("Drag with the left mouse button to paint");
+        });
+
+    egui::Window::new("Population")
+        .open(&mut model.chart_visible)
+        .show(&ctx, |ui| {
+            population_chart(ui, model.simulation.as_ref(), &mut model.chart_stacked)
+        });
+
+    // Don't paint when the mouse is used to interact with one of the windows
+    let pointer_on_ui = ctx.is_pointer_over_area() || ctx.is_using_pointer();
+    drop(ctx);
+
+    if !pointer_on_ui {
+        if model.scroll != 0.0 {
+            model
+                .camera
+                .zoom_at(mouse, model.scroll, window_size, image_size);
+        }
+
+        if app.mouse.buttons.right().is_down() || app.mouse.buttons.middle().is_down() {
+            let delta = (mouse.0 - model.last_mouse.0, mouse.1 - model.last_mouse.1);
+            model.camera.pan(delta, window_size, image_size);
+        }
+    }
+    model.scroll = 0.0;
+    model.last_mouse = mouse;
+
+    if model.palette_visible && app.mouse.buttons.left().is_down() && !pointer_on_ui {
+        let size = model.simulation.size();
+        for location in model.brush.cells((x as usize, y as usize), size) {
+            model.simulation.paint(location, model.paint_type);
+        }
+        render_image(model);
+    }
+
+    if recolor {
+        render_image(model);
+    }
+    if toggle_pause {
+        set_paused(app, model, !model.paused);
+    }
+    if step {
+        advance(app, model, 1);
+    }
+    if reset {
+        reset_simulation(model);
+    }
+}
+
+/// Runs the given number of rounds and renders the result
+fn advance(app: &App, model: &mut Model, rounds: usize) {
+    for _ in 0..rounds {
+        model.simulation.action();
+        record_frame(model);
+        if check_outcome(app, model) {
+            break;
+        }
+    }
+
+    if rounds > 0 {
+        render_image(model);
+    }
+}
+
+/// Reports a battle that just ended and applies `--on-end`, returns true if no more rounds should
+/// be played this frame
+fn check_outcome(app: &App, model: &mut Model) -> bool {
+    let outcome = model.simulation.outcome(model.settings.stalemate_rounds);
+    let previous = std::mem::replace(&mut model.outcome, outcome);
+    let outcome = match (previous, outcome) {
+        (None, Some(outcome)) => outcome,
+        _ => return false,
+    };
+
+    println!("{}", model.simulation.stats().describe_outcome(outcome));
+    match model.settings.on_end {
+        EndAction::Continue => false,
+        EndAction::Pause => {
+            set_paused(app, model, true);
+            true
+        }
+        EndAction::Exit => {
+            println!("{}", model.simulation.stats().summary());
+            app.quit();
+            true
+        }
+        EndAction::Restart => {
+            model.simulation.regenerate();
+            model.outcome = None;
+            false
+        }
+    }
+}
+
+fn set_paused(app: &App, model: &mut Model, paused: bool) {
+    model.paused = paused;
+    if paused {
+        app.set_loop_mode(nannou::app::LoopMode::wait());
+    } else {
+        app.set_loop_mode(nannou::app::LoopMode::refresh_sync());
+    }
+}
+
+fn render_image(model: &mut Model) {
+    if let nannou::image::DynamicImage::ImageRgb8(ref mut pixels) = model.image {
+        model.simulation.render(pixels, model.settings.render_mode);
+    }
+}
+
+fn record_frame(model: &mut Model) {
+    let Some(recorder) = &mut model.recorder else {
+        return;
+    };
+    if !recorder.is_due(model.simulation.round()) {
+        return;
+    }
+
+    if let nannou::image::DynamicImage::ImageRgb8(ref mut pixels) = model.image {
+        model.simulation.render(pixels, model.settings.render_mode);
+        if let Err(err) = recorder.record(pixels) {
+            eprintln!("Could not record frame: {err}");
+            model.recorder = None;
+        }
+    }
+}
+
+/// Starts a new grid from the settings of the control panel, keeping the current fight rules
+fn reset_simulation(model: &mut Model) {
+    let fighter_type = model.settings.fighter_type.clone();
+    let mut simulation = match create_simulation(&model.settings, &fighter_type) {
+        Ok(simulation) => simulation,
+        Err(err) => {
+            eprintln!("Could not create the battle: {err}");
+            return;
+        }
+    };
+    simulation.set_selection_algorithm(model.simulation.selection_algorithm());
+    simulation.set_filter_fight_candidates(model.simulation.filter_fight_candidates());
+    simulation.set_options(model.simulation.options());
+
+    let (width, height) = simulation.size();
+    model.image = nannou::image::DynamicImage::ImageRgb8(nannou::image::RgbImage::new(
+        width as u32,
+        height as u32,
+    ));
+    model.simulation = simulation;
+    model.fighter_type = fighter_type;
+    model.paint_type = 0;
+    model.camera = Camera::new();
+    model.outcome = None;
+    // Frames of a different grid don't belong in the same recording
+    if model.recorder.take().is_some() {
+        println!("Recording stopped");
+    }
+    render_image(model);
+}
+
+fn population_chart(ui: &mut egui::Ui, simulation: &dyn Simulation, stacked: &mut bool) {
+    use egui::plot::{Legend, Line, Plot, Value, Values};
+
+    ui.horizontal(|ui| {
+        ui.radio_value(stacked, false, "Lines");
+        ui.radio_value(stacked, true, "Stacked");
+    });
+
+    let stats = simulation.stats();
+    let types = stats.types();
+    let mut plot = Plot::new("population")
+        .legend(Legend::default())
+        .include_y(0.0)
+        .height(200.0);
+    for (i, name) in types.iter().enumerate() {
+        let points = stats.sampled(1000).map(|round| {
+            let count = if *stacked {
+                round.counts[..=i].iter().sum()
+            } else {
+                round.counts[i]
+            };
+            Value::new(round.round as f64, count as f64)
+        });
+        let [r, g, b] = simulation.type_color(i);
+        let mut line = Line::new(Values::from_values_iter(points))
+            .color(egui::Color32::from_rgb(r, g, b))
+            .name(name);
+        if *stacked {
+            line = line.fill(0.0);
+        }
+        plot = plot.line(line);
+    }
+    ui.add(plot);
+
+    let latest = match stats.latest() {
+        Some(latest) => latest,
+        None => return,
+    };
+    let total = latest.counts.iter().sum::<usize>().max(1);
+    egui::Grid::new("population_counts")
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, name) in types.iter().enumerate() {
+                let count = latest.counts[i];
+                ui.horizontal(|ui| {
+                    color_swatch(ui, simulation.type_color(i));
+                    ui.label(name);
+                });
+                ui.label(count.to_string());
+                ui.label(format!("{:.1}%", count as f64 * 100.0 / total as f64));
+                ui.end_row();
+            }
+        });
+}
+
+fn color_swatch(ui: &mut egui::Ui, color: [u8; 3]) {
+    let size = ui.spacing().icon_width;
+    let (rect, _response) =
+        ui.allocate_exact_size(egui::Vec2 { x: size, y: size }, egui::Sense::hover());
+    ui.painter().circle_filled(
+        rect.center(),
+        size * 0.5,
+        egui::Color32::from_rgb(color[0], color[1], color[2]),
+    );
 }
 
 fn get_image_ratio(
@@ -169,10 +701,10 @@ fn window_coords_to_image(
     window_pos: (f32, f32),
     window_size: (u32, u32),
     image_size: (u32, u32),
+    camera: &Camera,
 ) -> (u32, u32) {
-    // Consider image scaling to convert window coordinates into image coordinates.
-    let ratio = get_image_ratio(window_size, image_size);
-    let image_pos = (window_pos.0 / ratio, window_pos.1 / ratio);
+    // Consider image scaling, zoom and pan to convert window coordinates into image coordinates.
+    let image_pos = camera.window_to_image(window_pos, window_size, image_size);
 
     // Coordinates are relative to the middle of the image, we want them relative to the upper-left
     // corner.
@@ -200,21 +732,28 @@ fn window_coords_to_image(
     )
 }
 
-fn view<T>(app: &App, model: &Model<T>, frame: Frame) {
+fn view(app: &App, model: &Model, frame: Frame) {
     let texture = nannou::wgpu::Texture::from_image(app, &model.image);
 
-    let ratio = get_image_ratio(model.window_size, model.image.dimensions());
+    let scale = model
+        .camera
+        .scale(model.window_size, model.image.dimensions());
+    let (center_x, center_y) = model.camera.center;
 
     frame.clear(nannou::color::PURPLE);
 
-    let draw = app.draw();
-    if ratio == 1.0 {
-        draw.texture(&texture);
-    } else {
-        draw.texture(&texture)
-            .width(ratio * model.image.width() as f32)
-            .height(ratio * model.image.height() as f32);
-    }
+    // Nearest neighbour sampling keeps the cells crisp when zoomed in
+    let sampler = nannou::wgpu::SamplerBuilder::new()
+        .min_filter(nannou::wgpu::FilterMode::Nearest)
+        .mag_filter(nannou::wgpu::FilterMode::Nearest)
+        .into_descriptor();
+    let draw = app.draw().sampler(sampler);
+    draw.texture(&texture)
+        .x_y(-center_x * scale, -center_y * scale)
+        .w_h(
+            scale * model.image.width() as f32,
+            scale * model.image.height() as f32,
+        );
     draw.to_frame(app, &frame).unwrap();
 
     model.info.draw_to_frame(&frame).unwrap();
@@ -224,29 +763,94 @@ fn view<T>(app: &App, model: &Model<T>, frame: Frame) {
     }
 }
 
-fn key_pressed<T>(app: &App, model: &mut Model<T>, key: nannou::event::Key) {
+fn key_pressed(app: &App, model: &mut Model, key: nannou::event::Key) {
     if key == nannou::event::Key::Space {
-        model.paused = !model.paused;
-        if model.paused {
-            app.set_loop_mode(nannou::app::LoopMode::wait());
-        } else {
-            app.set_loop_mode(nannou::app::LoopMode::refresh_sync());
+        set_paused(app, model, !model.paused);
+    } else if key == nannou::event::Key::N && model.paused {
+        advance(app, model, 1);
+    } else if matches!(
+        key,
+        nannou::event::Key::Plus | nannou::event::Key::Equals | nannou::event::Key::NumpadAdd
+    ) {
+        model.speed = model.speed.faster();
+    } else if matches!(
+        key,
+        nannou::event::Key::Minus | nannou::event::Key::NumpadSubtract
+    ) {
+        model.speed = model.speed.slower();
+    } else if key == nannou::event::Key::F {
+        model.camera = Camera::new();
+    } else if key == nannou::event::Key::C {
+        model.controls_visible = !model.controls_visible;
+    } else if key == nannou::event::Key::P {
+        model.palette_visible = !model.palette_visible;
+    } else if key == nannou::event::Key::G {
+        model.chart_visible = !model.chart_visible;
+    } else if key == nannou::event::Key::R {
+        toggle_recording(model);
+    } else if key == nannou::event::Key::S {
+        let args = ARGS.get().unwrap();
+        let path = args
+            .snapshot
+            .as_deref()
+            .unwrap_or(Path::new("snapshot.json"));
+        match model.simulation.save_snapshot(&model.fighter_type, path) {
+            Ok(()) => println!("Snapshot saved to {}", path.display()),
+            Err(err) => eprintln!("Could not save snapshot: {err}"),
         }
     }
 }
 
-fn resized<T>(_app: &App, model: &mut Model<T>, size: nannou::glam::Vec2) {
+fn toggle_recording(model: &mut Model) {
+    if model.recorder.take().is_some() {
+        println!("Recording stopped");
+        return;
+    }
+
+    match Recorder::numbered(ARGS.get().unwrap(), model.recordings + 1) {
+        Ok(recorder) if recorder.is_active() => {
+            println!("Recording started");
+            model.recorder = Some(recorder);
+            model.recordings += 1;
+        }
+        Ok(_) => println!("Pass --frames or --gif to choose where recordings are written"),
+        Err(err) => eprintln!("Could not start recording: {err}"),
+    }
+}
+
+fn resized(_app: &App, model: &mut Model, size: nannou::glam::Vec2) {
     model.window_size = (size.x as u32, size.y as u32);
 }
 
-fn mouse_pressed<T>(_app: &App, model: &mut Model<T>, button: nannou::event::MouseButton) {
-    if button == nannou::event::MouseButton::Left {
+fn mouse_pressed(_app: &App, model: &mut Model, button: nannou::event::MouseButton) {
+    // While painting, the left mouse button is used for the brush instead
+    if button == nannou::event::MouseButton::Left && !model.palette_visible {
         model.info_visible = true;
     }
 }
 
-fn raw_event<T>(_app: &App, model: &mut Model<T>, event: &nannou::winit::event::WindowEvent) {
+fn mouse_wheel(
+    _app: &App,
+    model: &mut Model,
+    delta: nannou::event::MouseScrollDelta,
+    _phase: nannou::event::TouchPhase,
+) {
+    model.scroll += match delta {
+        nannou::event::MouseScrollDelta::LineDelta(_, y) => y,
+        // Touchpads scroll in pixels, roughly 50 of them make up a mouse wheel step
+        nannou::event::MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+    };
+}
+
+fn raw_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
     model.info.handle_raw_event(event);
 }
 
-fn exit<T>(_app: &App, _model: Model<T>) {}
+fn exit(_app: &App, model: Model) {
+    let args = ARGS.get().unwrap();
+    if let Some(stats) = &args.stats {
+        if let Err(err) = model.simulation.stats().save(stats) {
+            eprintln!("Could not write statistics: {err}");
+        }
+    }
+}
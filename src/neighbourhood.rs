@@ -0,0 +1,203 @@
+use crate::grid::{Boundary, Size};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+type Location = (usize, usize);
+type Offset = (isize, isize);
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NeighbourhoodKind {
+    /// Cells within the given Manhattan distance, the 4 direct neighbours for radius 1
+    #[default]
+    VonNeumann,
+    /// Cells within the given Chebyshev distance, the 8 surrounding cells for radius 1
+    Moore,
+    /// Hexagonal grid where odd rows are shifted half a cell to the right
+    Hexagonal,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Shape {
+    kind: NeighbourhoodKind,
+    radius: usize,
+}
+
+/// The cells a fighter can pick its opponent from
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "Shape", into = "Shape")]
+pub struct Neighbourhood {
+    shape: Shape,
+    // Offsets for fighters on even and odd rows, only hexagonal grids differ between the two
+    offsets: [Vec<Offset>; 2],
+}
+
+impl Neighbourhood {
+    pub fn new(kind: NeighbourhoodKind, radius: usize) -> Self {
+        let shape = Shape { kind, radius };
+        Neighbourhood {
+            shape,
+            offsets: [offsets(shape, 0), offsets(shape, 1)],
+        }
+    }
+
+    pub fn kind(&self) -> NeighbourhoodKind {
+        self.shape.kind
+    }
+
+    pub fn radius(&self) -> usize {
+        self.shape.radius
+    }
+
+    /// Neighbours of `location`, fewer near the edges of a grid that does not wrap around.
+    /// Hexagonal grids should have an even height to wrap around seamlessly.
+    pub fn neighbours(
+        &self,
+        location: Location,
+        size: Size,
+        boundary: Boundary,
+    ) -> impl Iterator<Item = Location> + '_ {
+        self.offsets[location.1 % 2]
+            .iter()
+            .filter_map(move |&offset| boundary.apply(location, offset, size))
+            .filter(move |&neighbour| neighbour != location)
+    }
+}
+
+impl Default for Neighbourhood {
+    fn default() -> Self {
+        Self::new(NeighbourhoodKind::VonNeumann, 1)
+    }
+}
+
+impl From<Shape> for Neighbourhood {
+    fn from(shape: Shape) -> Self {
+        Self::new(shape.kind, shape.radius)
+    }
+}
+
+impl From<Neighbourhood> for Shape {
+    fn from(neighbourhood: Neighbourhood) -> Self {
+        neighbourhood.shape
+    }
+}
+
+fn offsets(shape: Shape, row_parity: isize) -> Vec<Offset> {
+    let r = shape.radius as isize;
+    let mut offsets = Vec::new();
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let offset = match shape.kind {
+                NeighbourhoodKind::VonNeumann if dx.abs() + dy.abs() <= r => Some((dx, dy)),
+                NeighbourhoodKind::Moore => Some((dx, dy)),
+                // Here dx is the axial column offset, which is converted to an offset coordinate
+                // depending on whether the origin and target rows are shifted.
+                NeighbourhoodKind::Hexagonal if (dx + dy).abs() <= r => {
+                    let shift = (dy + row_parity - (row_parity + dy).rem_euclid(2)) / 2;
+                    Some((dx + shift, dy))
+                }
+                _ => None,
+            };
+
+            if let Some(offset) = offset.filter(|&offset| offset != (0, 0)) {
+                offsets.push(offset);
+            }
+        }
+    }
+
+    // Clockwise starting from the top, so the order of the neighbours does not depend on the shape
+    offsets.sort_by(|a, b| clockwise_key(*a).partial_cmp(&clockwise_key(*b)).unwrap());
+    offsets
+}
+
+fn clockwise_key((dx, dy): Offset) -> (f64, isize) {
+    let angle = (dx as f64).atan2(-dy as f64);
+    let angle = if angle < 0.0 { angle + 2.0 * PI } else { angle };
+    (angle, dx.abs() + dy.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(neighbourhood: &Neighbourhood, location: Location) -> Vec<Location> {
+        neighbourhood
+            .neighbours(location, (10, 8), Boundary::Torus)
+            .collect()
+    }
+
+    #[test]
+    fn test_von_neumann() {
+        let neighbourhood = Neighbourhood::default();
+        assert_eq!(
+            collect(&neighbourhood, (0, 0)),
+            vec![(0, 7), (1, 0), (0, 1), (9, 0)]
+        );
+
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::VonNeumann, 2);
+        let neighbours = collect(&neighbourhood, (5, 4));
+        assert_eq!(neighbours.len(), 12);
+        assert!(neighbours.contains(&(5, 2)));
+        assert!(neighbours.contains(&(6, 5)));
+        assert!(!neighbours.contains(&(7, 6)));
+    }
+
+    #[test]
+    fn test_moore() {
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::Moore, 1);
+        let neighbours = collect(&neighbourhood, (9, 7));
+        assert_eq!(neighbours.len(), 8);
+        assert!(neighbours.contains(&(0, 0)));
+        assert!(neighbours.contains(&(8, 6)));
+
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::Moore, 2);
+        assert_eq!(collect(&neighbourhood, (5, 4)).len(), 24);
+    }
+
+    #[test]
+    fn test_hexagonal() {
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::Hexagonal, 1);
+        let mut even = collect(&neighbourhood, (5, 4));
+        even.sort();
+        assert_eq!(even, vec![(4, 3), (4, 4), (4, 5), (5, 3), (5, 5), (6, 4)]);
+
+        let mut odd = collect(&neighbourhood, (5, 3));
+        odd.sort();
+        assert_eq!(odd, vec![(4, 3), (5, 2), (5, 4), (6, 2), (6, 3), (6, 4)]);
+
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::Hexagonal, 2);
+        assert_eq!(collect(&neighbourhood, (5, 4)).len(), 18);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::Moore, 1);
+        let count = |location, boundary| {
+            neighbourhood
+                .neighbours(location, (10, 8), boundary)
+                .count()
+        };
+        assert_eq!(count((0, 0), Boundary::Walls), 3);
+        assert_eq!(count((0, 4), Boundary::Walls), 5);
+        assert_eq!(count((5, 4), Boundary::Walls), 8);
+        assert_eq!(count((0, 0), Boundary::Cylinder), 5);
+        assert_eq!(count((0, 0), Boundary::Reflect), 8);
+
+        // Reflecting a radius 2 neighbourhood at the edge must not return the origin itself
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::VonNeumann, 2);
+        assert!(neighbourhood
+            .neighbours((1, 0), (10, 8), Boundary::Reflect)
+            .all(|neighbour| neighbour != (1, 0)));
+    }
+
+    #[test]
+    fn test_serialization() {
+        let neighbourhood = Neighbourhood::new(NeighbourhoodKind::Moore, 3);
+        let json = serde_json::to_string(&neighbourhood).unwrap();
+        let restored: Neighbourhood = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.kind(), NeighbourhoodKind::Moore);
+        assert_eq!(restored.radius(), 3);
+        assert_eq!(restored.offsets, neighbourhood.offsets);
+    }
+}
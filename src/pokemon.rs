@@ -1,11 +1,15 @@
 use crate::battle::Fighter;
-use crate::types::{Colored, GenerateRandomly};
+use crate::table_fighter::{BaseStats, FighterTable};
+use crate::types::{Colored, FighterOptions, GenerateRandomly, Typed};
+use crate::vitality::Vitality;
 use lazy_static::lazy_static;
 use rand::distributions::{Distribution, Uniform};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::iter;
 use strum::{EnumCount, FromRepr};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount, FromRepr)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount, FromRepr, Deserialize, Serialize)]
 #[repr(usize)]
 pub enum PokemonType {
     Normal,
@@ -40,7 +44,7 @@ impl From<PokemonType> for usize {
     }
 }
 
-impl From<PokemonType> for nannou::image::Rgb<u8> {
+impl From<PokemonType> for [u8; 3] {
     fn from(kind: PokemonType) -> Self {
         match kind {
             PokemonType::Normal => [168, 168, 120],
@@ -62,7 +66,6 @@ impl From<PokemonType> for nannou::image::Rgb<u8> {
             PokemonType::Steel => [184, 184, 208],
             PokemonType::Fairy => [240, 182, 188],
         }
-        .into()
     }
 }
 
@@ -92,54 +95,199 @@ fn get_effectiveness(attacker: PokemonType, defender: PokemonType) -> i32 {
     EFFICIENCY[attacker as usize][defender as usize]
 }
 
-#[derive(Clone)]
+/// Damage bonus in percent for attacks of one of the attacker's own types
+const SAME_TYPE_BONUS: i32 = 150;
+
+lazy_static! {
+    /// Roughly the average base stats of the Pokémon of every type in the games, read from the
+    /// Pokémon fighter table
+    static ref BASE_STATS: Vec<BaseStats> =
+        FighterTable::from_reader(include_str!("../tables/pokemon.json").as_bytes())
+            .expect("the Pokémon table should be valid")
+            .types
+            .iter()
+            .map(|kind| kind.stats.expect("every Pokémon type should have stats"))
+            .collect();
+}
+
+fn base_stats(kind: PokemonType) -> BaseStats {
+    BASE_STATS[kind as usize]
+}
+
+fn base_health(kind: PokemonType, use_base_stats: bool) -> i32 {
+    if use_base_stats {
+        base_stats(kind).health
+    } else {
+        80
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Pokemon {
-    health: i32,
+    vitality: Vitality,
     damage: i32,
     kind: PokemonType,
+    #[serde(default)]
+    secondary: Option<PokemonType>,
+    #[serde(default)]
+    options: FighterOptions,
 }
 
 impl Pokemon {
     pub fn new(kind: PokemonType) -> Self {
+        Self::with_secondary(kind, None)
+    }
+
+    /// A Pokémon with a second type, which only matters when it is attacked
+    pub fn with_secondary(kind: PokemonType, secondary: Option<PokemonType>) -> Self {
         Pokemon {
-            health: 80,
+            vitality: Vitality::new(base_health(kind, false)),
             damage: 40,
             kind,
+            secondary: secondary.filter(|&secondary| secondary != kind),
+            options: FighterOptions::default(),
         }
     }
 
-    fn reset(&mut self, kind: PokemonType) {
-        self.health = 80;
+    /// Effectiveness of an attack of type `kind` against both types of `self`
+    fn effectiveness_against(&self, kind: PokemonType) -> i32 {
+        let effectiveness = get_effectiveness(kind, self.kind);
+        match self.secondary {
+            Some(secondary) => effectiveness * get_effectiveness(kind, secondary) / 100,
+            None => effectiveness,
+        }
+    }
+
+    /// Effectiveness of the best move, either of one of the Pokémon's own types with the same type
+    /// bonus or a Normal move that every Pokémon knows
+    fn best_move(&self, defender: &Self) -> i32 {
+        iter::once(self.kind)
+            .chain(self.secondary)
+            .map(|kind| defender.effectiveness_against(kind) * SAME_TYPE_BONUS / 100)
+            .chain(iter::once(
+                defender.effectiveness_against(PokemonType::Normal),
+            ))
+            .max()
+            .unwrap()
+    }
+
+    /// Damage scaled by the attack of the attacker and the defense of the defender
+    fn stats_damage(&self, defender: &Self, effectiveness: i32) -> i32 {
+        let attack = base_stats(self.kind).attack;
+        let defense = base_stats(defender.kind).defense;
+        self.vitality.scale(self.damage) * attack * effectiveness / (defense * 100)
+    }
+
+    fn is_faster(&self, other: &Self) -> bool {
+        base_stats(self.kind).speed > base_stats(other.kind).speed
+    }
+
+    /// A second type for half of the Pokémon of type `kind`
+    fn random_secondary<R>(kind: PokemonType, rng: &mut R) -> Option<PokemonType>
+    where
+        R: Rng,
+    {
+        rng.gen_bool(0.5).then(|| {
+            // Any type but the primary one
+            let secondary = rng.gen_range(0..PokemonType::COUNT - 1);
+            PokemonType::from(secondary + usize::from(secondary >= kind as usize))
+        })
+    }
+
+    fn reset(&mut self, kind: PokemonType, secondary: Option<PokemonType>) {
+        self.vitality = Vitality::new(base_health(kind, self.options.base_stats));
         self.damage = 40;
         self.kind = kind;
+        self.secondary = secondary;
     }
 
-    fn take_damage(&mut self, damage: i32) -> bool {
-        self.health -= damage;
-
-        self.health <= 0
+    /// Full health at level 1
+    fn base_health(&self) -> i32 {
+        base_health(self.kind, self.options.base_stats)
     }
 }
 
 impl Fighter for Pokemon {
     fn should_fight(&self, defender: &Self) -> bool {
-        self.kind != defender.kind
+        (self.kind, self.secondary) != (defender.kind, defender.secondary)
     }
 
+    /// Attacks use the primary type, unless base stats let the attacker pick its best move
     fn get_effectiveness(&self, defender: &Self) -> i32 {
-        get_effectiveness(self.kind, defender.kind)
+        if self.options.base_stats {
+            self.best_move(defender)
+        } else {
+            defender.effectiveness_against(self.kind)
+        }
     }
 
-    fn fight(&self, defender: &mut Self) -> bool {
+    fn fight<R>(&self, defender: &mut Self, _rng: &mut R) -> bool
+    where
+        R: Rng,
+    {
         let effectiveness = self.get_effectiveness(defender);
-        let damage = self.damage * effectiveness / 100;
+        let damage = if self.options.base_stats {
+            self.stats_damage(defender, effectiveness)
+        } else {
+            self.vitality.scale(self.damage) * effectiveness / 100
+        };
 
-        let is_dead = defender.take_damage(damage);
+        let is_dead = defender.vitality.take_damage(damage);
         if is_dead {
-            defender.reset(self.kind);
+            defender.reset(self.kind, self.secondary);
         }
         is_dead
     }
+
+    fn strikes_first(&self, attacker: &Self) -> bool {
+        self.options.base_stats && self.is_faster(attacker)
+    }
+
+    fn strikes_back(&self, _attacker: &Self) -> bool {
+        self.options.base_stats
+    }
+
+    /// Switching dual types on gives half of the Pokémon a second type, switching them off takes
+    /// it away again. Switching base stats keeps the share of health left, so it does not heal or
+    /// hurt.
+    fn configure<R>(&mut self, options: &FighterOptions, rng: &mut R)
+    where
+        R: Rng,
+    {
+        if self.options == *options {
+            return;
+        }
+        if options.dual_types != self.options.dual_types {
+            self.secondary = match options.dual_types {
+                true => Self::random_secondary(self.kind, rng),
+                false => None,
+            };
+        }
+        let old = self.base_health();
+        self.options = *options;
+        self.vitality.rebase(old, self.base_health());
+    }
+
+    fn health(&self) -> f32 {
+        self.vitality.share(self.base_health())
+    }
+
+    fn regenerate(&mut self, rate: f64) {
+        self.vitality.regenerate(self.base_health(), rate);
+    }
+
+    fn level(&self) -> u32 {
+        self.vitality.level()
+    }
+
+    fn gain_experience(&mut self) {
+        self.vitality.gain_experience(self.base_health());
+    }
+
+    fn inherit_experience(&mut self, winner: &Self) {
+        self.vitality
+            .inherit_experience(&winner.vitality, self.base_health());
+    }
 }
 
 lazy_static! {
@@ -157,20 +305,57 @@ impl GenerateRandomly for Pokemon {
 }
 
 impl Colored for Pokemon {
-    fn color(&self) -> nannou::image::Rgb<u8> {
+    /// A second type is blended in at a third, so the primary type stays recognizable
+    fn color(&self) -> [u8; 3] {
+        let primary: [u8; 3] = self.kind.into();
+        match self.secondary {
+            Some(secondary) => {
+                let secondary: [u8; 3] = secondary.into();
+                [0, 1, 2].map(|i| ((2 * primary[i] as u16 + secondary[i] as u16) / 3) as u8)
+            }
+            None => primary,
+        }
+    }
+}
+
+impl Typed for Pokemon {
+    fn type_index(&self) -> usize {
         self.kind.into()
     }
+
+    fn type_names() -> Vec<String> {
+        (0..PokemonType::COUNT)
+            .map(|kind| format!("{:?}", PokemonType::from(kind)))
+            .collect()
+    }
+
+    fn from_type_index(index: usize) -> Self {
+        Self::new(index.into())
+    }
+
+    /// Offspring have the types and options of their parent
+    fn offspring(&self) -> Self {
+        let mut offspring = self.clone();
+        offspring.reset(self.kind, self.secondary);
+        offspring
+    }
 }
 
 impl core::fmt::Display for Pokemon {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "{:?}", self.kind)
+        match self.secondary {
+            Some(secondary) => write!(f, "{:?}/{:?}", self.kind, secondary)?,
+            None => write!(f, "{:?}", self.kind)?,
+        }
+        write!(f, "{}", self.vitality)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     #[test]
     fn convert_type_from_usize() {
@@ -196,45 +381,158 @@ mod tests {
 
     #[test]
     fn test_get_color() {
+        assert_eq!(Pokemon::new(PokemonType::Normal).color(), [168, 168, 120]);
+        assert_eq!(Pokemon::new(PokemonType::Fairy).color(), [240, 182, 188]);
+    }
+
+    #[test]
+    fn test_dual_types() {
+        let fire = Pokemon::new(PokemonType::Fire);
+        let grass_steel = Pokemon::with_secondary(PokemonType::Grass, Some(PokemonType::Steel));
+        let water_grass = Pokemon::with_secondary(PokemonType::Water, Some(PokemonType::Grass));
+        assert_eq!(fire.get_effectiveness(&grass_steel), 400);
+        assert_eq!(fire.get_effectiveness(&water_grass), 100);
         assert_eq!(
-            Pokemon::new(PokemonType::Normal).color(),
-            nannou::image::Rgb([168, 168, 120])
+            Pokemon::new(PokemonType::Electric).get_effectiveness(&water_grass),
+            100
         );
+        assert_eq!(format!("{grass_steel}"), "Grass/Steel");
+        assert_eq!(format!("{}", grass_steel.offspring()), "Grass/Steel");
+        assert_eq!(grass_steel.color(), [141, 194, 122]);
+        assert_eq!(grass_steel.type_index(), PokemonType::Grass as usize);
+        assert!(grass_steel.should_fight(&Pokemon::new(PokemonType::Grass)));
+        assert!(
+            Pokemon::with_secondary(PokemonType::Ice, Some(PokemonType::Ice))
+                .secondary
+                .is_none()
+        );
+
+        // Defeated Pokémon take both types of the attacker
+        let mut defender = Pokemon::new(PokemonType::Water);
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        assert!(grass_steel.fight(&mut defender, &mut rng));
+        assert_eq!(format!("{defender}"), "Grass/Steel");
+        assert!(!grass_steel.should_fight(&defender));
+
+        let dual_types = FighterOptions {
+            dual_types: true,
+            ..FighterOptions::default()
+        };
+        let mut bugs = vec![Pokemon::new(PokemonType::Bug); 100];
+        bugs.iter_mut()
+            .for_each(|bug| bug.configure(&dual_types, &mut rng));
+        let dual = bugs.iter().filter(|bug| bug.secondary.is_some()).count();
+        assert!((20..80).contains(&dual));
+        assert!(bugs
+            .iter()
+            .all(|bug| bug.secondary != Some(PokemonType::Bug)));
+
+        // Configuring again changes nothing, switching dual types off removes the second types
+        bugs.iter_mut()
+            .for_each(|bug| bug.configure(&dual_types, &mut rng));
         assert_eq!(
-            Pokemon::new(PokemonType::Fairy).color(),
-            nannou::image::Rgb([240, 182, 188])
+            bugs.iter().filter(|bug| bug.secondary.is_some()).count(),
+            dual
         );
+        let mut bug = bugs[0].clone();
+        bug.configure(&FighterOptions::default(), &mut rng);
+        assert!(bug.secondary.is_none());
     }
 
     #[test]
     fn test_damage() {
         let mut p1 = Pokemon::new(PokemonType::Normal);
-        let health = p1.health;
-        let dead = p1.take_damage(40);
+        let health = p1.vitality.health();
+        let dead = p1.vitality.take_damage(40);
 
-        assert_ne!(health, p1.health);
+        assert_ne!(health, p1.vitality.health());
         assert!(!dead);
 
-        let health = p1.health;
-        let dead = p1.take_damage(40);
+        let health = p1.vitality.health();
+        let dead = p1.vitality.take_damage(40);
 
-        assert_ne!(health, p1.health);
+        assert_ne!(health, p1.vitality.health());
         assert!(dead);
     }
 
     #[test]
     fn test_reset() {
         let mut p1 = Pokemon::new(PokemonType::Normal);
-        p1.reset(PokemonType::Fire);
+        p1.reset(PokemonType::Fire, None);
         assert_eq!(p1.kind, PokemonType::Fire);
 
-        let dead = p1.take_damage(80);
+        let dead = p1.vitality.take_damage(80);
         assert!(dead);
 
-        p1.reset(PokemonType::Dragon);
+        p1.reset(PokemonType::Dragon, None);
         assert_eq!(p1.kind, PokemonType::Dragon);
 
-        let dead = p1.take_damage(40);
+        let dead = p1.vitality.take_damage(40);
         assert!(!dead);
     }
+
+    #[test]
+    fn test_regenerate() {
+        let mut pokemon = Pokemon::new(PokemonType::Water);
+        pokemon.vitality.take_damage(60);
+        assert_eq!(pokemon.health(), 0.25);
+        pokemon.regenerate(0.25);
+        assert_eq!(pokemon.health(), 0.5);
+        pokemon.regenerate(1.0);
+        assert_eq!(pokemon.vitality.health(), 80);
+    }
+
+    #[test]
+    fn test_leveling() {
+        let mut winner = Pokemon::new(PokemonType::Water);
+        (0..6).for_each(|_| winner.gain_experience());
+        assert_eq!(winner.level(), 3);
+        assert_eq!(winner.vitality.health(), 96);
+        assert_eq!(format!("{winner}"), "Water, level 3");
+
+        let mut defender = Pokemon::new(PokemonType::Fire);
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        assert!(winner.fight(&mut defender, &mut rng));
+        defender.inherit_experience(&winner);
+        assert_eq!(defender.level(), 2);
+        assert_eq!(defender.health(), 1.0);
+        assert_eq!(defender.vitality.health(), 88);
+    }
+
+    #[test]
+    fn test_base_stats() {
+        assert_eq!(BASE_STATS.len(), PokemonType::COUNT);
+        assert_eq!(base_stats(PokemonType::Steel).defense, 110);
+
+        let pokemon = |kind, secondary| Pokemon::with_secondary(kind, secondary);
+        let fire = pokemon(PokemonType::Fire, None);
+        let grass_steel = pokemon(PokemonType::Grass, Some(PokemonType::Steel));
+        let water_grass = pokemon(PokemonType::Water, Some(PokemonType::Grass));
+
+        // Own types get the same type bonus, a Normal move is used when it is better
+        assert_eq!(fire.best_move(&grass_steel), 600);
+        assert_eq!(fire.best_move(&pokemon(PokemonType::Water, None)), 100);
+        assert_eq!(
+            water_grass.best_move(&pokemon(PokemonType::Ground, None)),
+            300
+        );
+        assert_eq!(
+            pokemon(PokemonType::Normal, None).best_move(&pokemon(PokemonType::Ghost, None)),
+            0
+        );
+
+        let fighting = pokemon(PokemonType::Fighting, None);
+        let steel = pokemon(PokemonType::Steel, None);
+        assert_eq!(
+            fighting.stats_damage(&steel, fighting.best_move(&steel)),
+            109
+        );
+        let rock = pokemon(PokemonType::Rock, None);
+        assert_eq!(rock.stats_damage(&rock, rock.best_move(&rock)), 54);
+
+        let electric = pokemon(PokemonType::Electric, None);
+        assert!(electric.is_faster(&steel));
+        assert!(!steel.is_faster(&electric));
+        assert!(!electric.is_faster(&pokemon(PokemonType::Flying, None)));
+    }
 }
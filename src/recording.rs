@@ -0,0 +1,142 @@
+use crate::args::Args;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, DynamicImage, Frame, RgbImage};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Writes rendered frames to numbered PNG files and/or an animated GIF
+pub struct Recorder {
+    frames: Option<PathBuf>,
+    gif: Option<GifEncoder<BufWriter<File>>>,
+    interval: usize,
+    delay: Delay,
+    scale: u32,
+    frame: usize,
+    /// Added to the file names of every recording after the first, empty for the first one
+    suffix: String,
+}
+
+impl Recorder {
+    pub fn new(args: &Args) -> io::Result<Self> {
+        Self::numbered(args, 1)
+    }
+
+    /// Like `new`, but recordings after the first get their number added to the file names, so
+    /// they don't overwrite the earlier ones
+    pub fn numbered(args: &Args, number: usize) -> io::Result<Self> {
+        if let Some(frames) = &args.frames {
+            fs::create_dir_all(frames)?;
+        }
+
+        let suffix = match number {
+            1 => String::new(),
+            number => format!("_{number}"),
+        };
+        let gif = match &args.gif {
+            Some(path) => {
+                let path = with_suffix(path, &suffix);
+                // Speed 10 is the quantizer's default trade-off between quality and encoding time
+                let mut encoder =
+                    GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), 10);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(io::Error::other)?;
+                Some(encoder)
+            }
+            None => None,
+        };
+
+        Ok(Recorder {
+            frames: args.frames.clone(),
+            gif,
+            interval: args.frame_interval.max(1),
+            delay: Delay::from_numer_denom_ms(args.frame_delay as u32, 1),
+            scale: args.frame_scale.max(1) as u32,
+            frame: 0,
+            suffix,
+        })
+    }
+
+    /// Returns false when neither a frame directory nor a GIF file was requested
+    pub fn is_active(&self) -> bool {
+        self.frames.is_some() || self.gif.is_some()
+    }
+
+    /// Returns true if the frame of the given round should be recorded
+    pub fn is_due(&self, round: usize) -> bool {
+        self.is_active() && round.is_multiple_of(self.interval)
+    }
+
+    pub fn record(&mut self, image: &RgbImage) -> io::Result<()> {
+        let scaled;
+        let image = if self.scale > 1 {
+            let (width, height) = image.dimensions();
+            scaled = imageops::resize(
+                image,
+                width * self.scale,
+                height * self.scale,
+                FilterType::Nearest,
+            );
+            &scaled
+        } else {
+            image
+        };
+
+        if let Some(frames) = &self.frames {
+            let path = frames.join(format!("frame{}_{:06}.png", self.suffix, self.frame));
+            image.save(path).map_err(io::Error::other)?;
+        }
+
+        if let Some(gif) = &mut self.gif {
+            let rgba = DynamicImage::ImageRgb8(image.clone()).into_rgba8();
+            gif.encode_frame(Frame::from_parts(rgba, 0, 0, self.delay))
+                .map_err(io::Error::other)?;
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+/// `battle.gif` with the suffix `_2` becomes `battle_2.gif`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_numbered_recordings() {
+        assert_eq!(
+            with_suffix(Path::new("out/battle.gif"), "_2"),
+            Path::new("out/battle_2.gif")
+        );
+        assert_eq!(with_suffix(Path::new("battle"), ""), Path::new("battle"));
+
+        let frames = std::env::temp_dir().join(format!(
+            "poke-fighting-rust-recording-test-{}",
+            std::process::id()
+        ));
+        let args = Args::parse_from(["test", "--frames", frames.to_str().unwrap()]);
+        let image = RgbImage::new(4, 4);
+        Recorder::new(&args).unwrap().record(&image).unwrap();
+        Recorder::numbered(&args, 2)
+            .unwrap()
+            .record(&image)
+            .unwrap();
+        assert!(frames.join("frame_000000.png").exists());
+        assert!(frames.join("frame_2_000000.png").exists());
+        fs::remove_dir_all(&frames).unwrap();
+    }
+}
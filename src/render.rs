@@ -0,0 +1,53 @@
+use crate::battle::{Battle, Fighter};
+use crate::experience::MAX_LEVEL;
+use crate::types::Colored;
+use clap::ValueEnum;
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+const WALL_COLOR: [u8; 3] = [96, 96, 96];
+
+/// What the color of a fighter shows
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenderMode {
+    /// Only the type
+    #[default]
+    Type,
+    /// The type, dimmed by how much health is left
+    Health,
+    /// The type, brighter the higher the level of the fighter
+    Level,
+}
+
+/// Draws every fighter in its color, empty cells are black
+pub fn render<T, R>(battle: &Battle<T, R>, pixels: &mut RgbImage, mode: RenderMode)
+where
+    T: Colored + Fighter,
+{
+    for (x, y, pixel) in pixels.enumerate_pixels_mut() {
+        let location = (x as usize, y as usize);
+        *pixel = match battle.fighter(location) {
+            Some(fighter) => fighter_color(fighter, mode).into(),
+            None if battle.is_wall(location) => WALL_COLOR.into(),
+            None => [0, 0, 0].into(),
+        };
+    }
+}
+
+fn fighter_color<T>(fighter: &T, mode: RenderMode) -> [u8; 3]
+where
+    T: Colored + Fighter,
+{
+    let color = fighter.color();
+    let brightness = match mode {
+        RenderMode::Type => return color,
+        RenderMode::Health => fighter.health().clamp(0.0, 1.0),
+        // Fighters at level 1 are drawn at 40% brightness
+        RenderMode::Level => {
+            let level = fighter.level().clamp(1, MAX_LEVEL);
+            0.4 + 0.6 * (level - 1) as f32 / (MAX_LEVEL - 1) as f32
+        }
+    };
+    color.map(|channel| (channel as f32 * brightness) as u8)
+}
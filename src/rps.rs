@@ -1,11 +1,13 @@
 use crate::battle::Fighter;
-use crate::types::{Colored, GenerateRandomly};
+use crate::types::{Colored, GenerateRandomly, Typed};
+use crate::vitality::Vitality;
 use lazy_static::lazy_static;
 use rand::distributions::{Distribution, Uniform};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use strum::{EnumCount, FromRepr};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount, FromRepr)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount, FromRepr, Deserialize, Serialize)]
 #[repr(usize)]
 pub enum RPSType {
     Rock,
@@ -25,14 +27,13 @@ impl From<RPSType> for usize {
     }
 }
 
-impl From<RPSType> for nannou::image::Rgb<u8> {
+impl From<RPSType> for [u8; 3] {
     fn from(kind: RPSType) -> Self {
         match kind {
             RPSType::Rock => [128, 0, 0],
             RPSType::Paper => [0, 0, 128],
             RPSType::Scissor => [0, 128, 0],
         }
-        .into()
     }
 }
 
@@ -47,33 +48,30 @@ fn get_effectiveness(attacker: RPSType, defender: RPSType) -> i32 {
     EFFICIENCY[attacker as usize][defender as usize]
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct RPS {
-    health: i32,
+    vitality: Vitality,
     damage: i32,
     kind: RPSType,
 }
 
+/// Full health at level 1
+const BASE_HEALTH: i32 = 100;
+
 impl RPS {
     pub fn new(kind: RPSType) -> Self {
         RPS {
-            health: 100,
+            vitality: Vitality::new(BASE_HEALTH),
             damage: 100,
             kind,
         }
     }
 
     fn reset(&mut self, kind: RPSType) {
-        self.health = 100;
+        self.vitality = Vitality::new(BASE_HEALTH);
         self.damage = 100;
         self.kind = kind;
     }
-
-    fn take_damage(&mut self, damage: i32) -> bool {
-        self.health -= damage;
-
-        self.health <= 0
-    }
 }
 
 impl Fighter for RPS {
@@ -85,16 +83,40 @@ impl Fighter for RPS {
         get_effectiveness(self.kind, defender.kind)
     }
 
-    fn fight(&self, defender: &mut Self) -> bool {
+    fn fight<R>(&self, defender: &mut Self, _rng: &mut R) -> bool
+    where
+        R: Rng,
+    {
         let effectiveness = self.get_effectiveness(defender);
-        let damage = self.damage * effectiveness / 100;
+        let damage = self.vitality.scale(self.damage) * effectiveness / 100;
 
-        let is_dead = defender.take_damage(damage);
+        let is_dead = defender.vitality.take_damage(damage);
         if is_dead {
             defender.reset(self.kind);
         }
         is_dead
     }
+
+    fn health(&self) -> f32 {
+        self.vitality.share(BASE_HEALTH)
+    }
+
+    fn regenerate(&mut self, rate: f64) {
+        self.vitality.regenerate(BASE_HEALTH, rate);
+    }
+
+    fn level(&self) -> u32 {
+        self.vitality.level()
+    }
+
+    fn gain_experience(&mut self) {
+        self.vitality.gain_experience(BASE_HEALTH);
+    }
+
+    fn inherit_experience(&mut self, winner: &Self) {
+        self.vitality
+            .inherit_experience(&winner.vitality, BASE_HEALTH);
+    }
 }
 
 lazy_static! {
@@ -112,14 +134,30 @@ impl GenerateRandomly for RPS {
 }
 
 impl Colored for RPS {
-    fn color(&self) -> nannou::image::Rgb<u8> {
+    fn color(&self) -> [u8; 3] {
         self.kind.into()
     }
 }
 
+impl Typed for RPS {
+    fn type_index(&self) -> usize {
+        self.kind.into()
+    }
+
+    fn type_names() -> Vec<String> {
+        (0..RPSType::COUNT)
+            .map(|kind| format!("{:?}", RPSType::from(kind)))
+            .collect()
+    }
+
+    fn from_type_index(index: usize) -> Self {
+        Self::new(index.into())
+    }
+}
+
 impl core::fmt::Display for RPS {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "{:?}", self.kind)
+        write!(f, "{:?}{}", self.kind, self.vitality)
     }
 }
 
@@ -171,33 +209,24 @@ mod tests {
 
     #[test]
     fn test_get_color() {
-        assert_eq!(
-            RPS::new(RPSType::Rock).color(),
-            nannou::image::Rgb([128, 0, 0])
-        );
-        assert_eq!(
-            RPS::new(RPSType::Paper).color(),
-            nannou::image::Rgb([0, 0, 128])
-        );
-        assert_eq!(
-            RPS::new(RPSType::Scissor).color(),
-            nannou::image::Rgb([0, 128, 0])
-        );
+        assert_eq!(RPS::new(RPSType::Rock).color(), [128, 0, 0]);
+        assert_eq!(RPS::new(RPSType::Paper).color(), [0, 0, 128]);
+        assert_eq!(RPS::new(RPSType::Scissor).color(), [0, 128, 0]);
     }
 
     #[test]
     fn test_damage() {
         let mut p1 = RPS::new(RPSType::Rock);
-        let health = p1.health;
-        let dead = p1.take_damage(0);
+        let health = p1.vitality.health();
+        let dead = p1.vitality.take_damage(0);
 
-        assert_eq!(health, p1.health);
+        assert_eq!(health, p1.vitality.health());
         assert!(!dead);
 
-        let health = p1.health;
-        let dead = p1.take_damage(100);
+        let health = p1.vitality.health();
+        let dead = p1.vitality.take_damage(100);
 
-        assert_ne!(health, p1.health);
+        assert_ne!(health, p1.vitality.health());
         assert!(dead);
     }
 
@@ -207,13 +236,13 @@ mod tests {
         p1.reset(RPSType::Paper);
         assert_eq!(p1.kind, RPSType::Paper);
 
-        let dead = p1.take_damage(100);
+        let dead = p1.vitality.take_damage(100);
         assert!(dead);
 
         p1.reset(RPSType::Scissor);
         assert_eq!(p1.kind, RPSType::Scissor);
 
-        let dead = p1.take_damage(0);
+        let dead = p1.vitality.take_damage(0);
         assert!(!dead);
     }
 }
@@ -0,0 +1,181 @@
+use crate::args::{Args, FighterType};
+use crate::battle::{Battle, Fighter, SelectionAlgorithm};
+use crate::color_fight::ColorFighter;
+use crate::grid::Size;
+use crate::pokemon::Pokemon;
+use crate::render::{render, RenderMode};
+use crate::rps::RPS;
+use crate::snapshot::save_snapshot;
+use crate::stats::{Outcome, Statistics};
+use crate::street_fighter::StreetFighter;
+use crate::table_fighter::TableFighter;
+use crate::types::{Colored, FighterOptions, GenerateRandomly, Typed};
+use core::fmt::Display;
+use image::RgbImage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+type Location = (usize, usize);
+
+/// A battle of any fighter type, so the fighter type can be changed while the program runs
+pub trait Simulation {
+    fn action(&mut self);
+    fn render(&self, pixels: &mut RgbImage, mode: RenderMode);
+    fn size(&self) -> Size;
+    fn round(&self) -> usize;
+    fn stats(&self) -> &Statistics;
+    fn outcome(&self, stalemate_rounds: usize) -> Option<Outcome>;
+    /// Starts again with a new grid in the same layout and with the same settings
+    fn regenerate(&mut self);
+    fn selection_algorithm(&self) -> SelectionAlgorithm;
+    fn set_selection_algorithm(&mut self, selection_algorithm: SelectionAlgorithm);
+    fn filter_fight_candidates(&self) -> bool;
+    fn set_filter_fight_candidates(&mut self, filter_fight_candidates: bool);
+    fn options(&self) -> FighterOptions;
+    fn set_options(&mut self, options: FighterOptions);
+    /// Description and color of the fighter at `location`
+    fn describe(&self, location: Location) -> Option<(String, [u8; 3])>;
+    /// Color of a fresh fighter of the given type
+    fn type_color(&self, type_index: usize) -> [u8; 3];
+    /// Replaces the fighter at `location` with a fresh fighter of the given type
+    fn paint(&mut self, location: Location, type_index: usize);
+    fn save_snapshot(&self, fighter_type: &FighterType, path: &Path) -> io::Result<()>;
+}
+
+impl<T> Simulation for Battle<T>
+where
+    T: Colored + Fighter + GenerateRandomly + Typed + Clone + Send + Display + Serialize,
+{
+    fn action(&mut self) {
+        Battle::action(self);
+    }
+
+    fn render(&self, pixels: &mut RgbImage, mode: RenderMode) {
+        render(self, pixels, mode);
+    }
+
+    fn size(&self) -> Size {
+        Battle::size(self)
+    }
+
+    fn round(&self) -> usize {
+        Battle::round(self)
+    }
+
+    fn stats(&self) -> &Statistics {
+        Battle::stats(self)
+    }
+
+    fn outcome(&self, stalemate_rounds: usize) -> Option<Outcome> {
+        Battle::outcome(self, stalemate_rounds)
+    }
+
+    fn regenerate(&mut self) {
+        Battle::regenerate(self);
+    }
+
+    fn selection_algorithm(&self) -> SelectionAlgorithm {
+        Battle::selection_algorithm(self)
+    }
+
+    fn set_selection_algorithm(&mut self, selection_algorithm: SelectionAlgorithm) {
+        Battle::set_selection_algorithm(self, selection_algorithm);
+    }
+
+    fn filter_fight_candidates(&self) -> bool {
+        Battle::filter_fight_candidates(self)
+    }
+
+    fn set_filter_fight_candidates(&mut self, filter_fight_candidates: bool) {
+        Battle::set_filter_fight_candidates(self, filter_fight_candidates);
+    }
+
+    fn options(&self) -> FighterOptions {
+        Battle::options(self)
+    }
+
+    fn set_options(&mut self, options: FighterOptions) {
+        Battle::set_options(self, options);
+    }
+
+    fn describe(&self, location: Location) -> Option<(String, [u8; 3])> {
+        self.fighter(location)
+            .map(|fighter| (fighter.to_string(), fighter.color()))
+    }
+
+    fn type_color(&self, type_index: usize) -> [u8; 3] {
+        T::from_type_index(type_index).color()
+    }
+
+    fn paint(&mut self, location: Location, type_index: usize) {
+        self.set_fighter(location, T::from_type_index(type_index));
+    }
+
+    fn save_snapshot(&self, fighter_type: &FighterType, path: &Path) -> io::Result<()> {
+        save_snapshot(self, fighter_type, path)
+    }
+}
+
+/// Creates the battle described by `args` for the given fighter type
+pub fn create_simulation(
+    args: &Args,
+    fighter_type: &FighterType,
+) -> io::Result<Box<dyn Simulation>> {
+    Ok(match fighter_type {
+        FighterType::Pokemon => boxed::<Pokemon>(args, fighter_type)?,
+        FighterType::RockPaperScissors => boxed::<RPS>(args, fighter_type)?,
+        FighterType::StreetFighter => boxed::<StreetFighter>(args, fighter_type)?,
+        FighterType::ColorFighter => boxed::<ColorFighter>(args, fighter_type)?,
+        FighterType::Custom if TableFighter::is_table_loaded() => {
+            boxed::<TableFighter>(args, fighter_type)?
+        }
+        FighterType::Custom => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the custom fighter type needs a --table file",
+            ))
+        }
+    })
+}
+
+fn boxed<T>(args: &Args, fighter_type: &FighterType) -> io::Result<Box<dyn Simulation>>
+where
+    T: 'static
+        + Colored
+        + Fighter
+        + GenerateRandomly
+        + Typed
+        + Clone
+        + Send
+        + Display
+        + Serialize
+        + DeserializeOwned,
+{
+    Ok(Box::new(args.create_battle::<T>(fighter_type)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_create_simulation() {
+        let args = Args::parse_from(["test", "-x", "48", "-y", "32", "-s", "1"]);
+        let mut simulation = create_simulation(&args, &FighterType::RockPaperScissors).unwrap();
+        assert_eq!(simulation.size(), (48, 32));
+        assert_eq!(simulation.stats().types(), ["Rock", "Paper", "Scissor"]);
+
+        simulation.paint((3, 4), 2);
+        assert_eq!(
+            simulation.describe((3, 4)),
+            Some(("Scissor".to_string(), simulation.type_color(2)))
+        );
+
+        simulation.set_selection_algorithm(SelectionAlgorithm::RandomNeighbour);
+        simulation.action();
+        assert_eq!(simulation.round(), 1);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::args::FighterType;
+use crate::battle::{Battle, Fighter};
+use crate::types::Typed;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Bumped whenever the snapshot layout changes in a way older files cannot be read with
+const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Deserialize, Serialize)]
+struct Snapshot<B> {
+    version: u32,
+    fighter_type: FighterType,
+    /// Names of the fighter types, snapshots of the custom fighter type only fit the same table
+    types: Vec<String>,
+    battle: B,
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+    fighter_type: FighterType,
+}
+
+pub fn save_snapshot<T, R>(
+    battle: &Battle<T, R>,
+    fighter_type: &FighterType,
+    path: &Path,
+) -> io::Result<()>
+where
+    T: Typed + Serialize,
+    R: Serialize,
+{
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION,
+        fighter_type: fighter_type.clone(),
+        types: T::type_names(),
+        battle,
+    };
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &snapshot)?;
+    writer.flush()
+}
+
+/// Reads only the fighter type of a snapshot, so the caller knows which `Battle` to load it into
+pub fn snapshot_fighter_type(path: &Path) -> io::Result<FighterType> {
+    let reader = BufReader::new(File::open(path)?);
+    let header: SnapshotHeader = serde_json::from_reader(reader)?;
+    check_version(header.version)?;
+    Ok(header.fighter_type)
+}
+
+pub fn load_snapshot<T, R>(path: &Path, fighter_type: &FighterType) -> io::Result<Battle<T, R>>
+where
+    T: Fighter + Typed + DeserializeOwned,
+    R: Rng + DeserializeOwned,
+{
+    let reader = BufReader::new(File::open(path)?);
+    let snapshot: Snapshot<Battle<T, R>> = serde_json::from_reader(reader)?;
+    check_version(snapshot.version)?;
+
+    if snapshot.fighter_type != *fighter_type {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "snapshot contains {:?} fighters, expected {:?}",
+                snapshot.fighter_type, fighter_type
+            ),
+        ));
+    }
+
+    let types = T::type_names();
+    if snapshot.types != types {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "snapshot contains the types {}, expected {}",
+                snapshot.types.join(", "),
+                types.join(", ")
+            ),
+        ));
+    }
+
+    snapshot.battle.validate()?;
+    Ok(snapshot.battle)
+}
+
+fn check_version(version: u32) -> io::Result<()> {
+    if version != SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported snapshot version {version}, expected {SNAPSHOT_VERSION}"),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::SelectionAlgorithm;
+    use crate::render::{render, RenderMode};
+    use crate::street_fighter::StreetFighter;
+    use image::RgbImage;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_restored_battle_continues_identically() {
+        let path = std::env::temp_dir().join(format!(
+            "poke-fighting-rust-snapshot-test-{}.json",
+            std::process::id()
+        ));
+        let rng = ChaCha8Rng::seed_from_u64(11);
+        let mut battle =
+            Battle::<StreetFighter>::new(48, 32, SelectionAlgorithm::RandomNeighbour, true, rng);
+        for _ in 0..5 {
+            battle.action();
+        }
+
+        save_snapshot(&battle, &FighterType::StreetFighter, &path).unwrap();
+        assert_eq!(
+            snapshot_fighter_type(&path).unwrap(),
+            FighterType::StreetFighter
+        );
+        assert!(load_snapshot::<StreetFighter, ChaCha8Rng>(&path, &FighterType::Pokemon).is_err());
+
+        let mut restored: Battle<StreetFighter> =
+            load_snapshot(&path, &FighterType::StreetFighter).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.round(), 5);
+
+        for _ in 0..5 {
+            battle.action();
+            restored.action();
+        }
+
+        let mut expected = RgbImage::new(48, 32);
+        let mut actual = RgbImage::new(48, 32);
+        render(&battle, &mut expected, RenderMode::Health);
+        render(&restored, &mut actual, RenderMode::Health);
+        assert_eq!(expected, actual);
+        assert_eq!(
+            battle.stats().latest().unwrap().counts,
+            restored.stats().latest().unwrap().counts
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RoundStats {
+    pub round: usize,
+    pub fights: usize,
+    /// Fights where the defender was defeated and converted to the attacker's type
+    pub conversions: usize,
+    /// Number of living fighters per type, indexed like `Statistics::types`
+    pub counts: Vec<usize>,
+}
+
+/// Why a battle stopped changing
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// Only fighters of the type with this index are left
+    Extinction(usize),
+    /// No fighter was converted for this many rounds
+    Stalemate(usize),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Statistics {
+    types: Vec<String>,
+    rounds: Vec<RoundStats>,
+}
+
+impl Statistics {
+    pub fn new(types: Vec<String>) -> Self {
+        Statistics {
+            types,
+            rounds: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, round: RoundStats) {
+        self.rounds.push(round);
+    }
+
+    /// Corrects the counts of the latest round after the grid changed outside of a round
+    pub fn set_latest_counts(&mut self, counts: Vec<usize>) {
+        if let Some(latest) = self.rounds.last_mut() {
+            latest.counts = counts;
+        }
+    }
+
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    pub fn rounds(&self) -> &[RoundStats] {
+        &self.rounds
+    }
+
+    pub fn latest(&self) -> Option<&RoundStats> {
+        self.rounds.last()
+    }
+
+    /// Whether the latest round ended the battle, a stalemate needs `stalemate_rounds` rounds in a
+    /// row without conversions
+    pub fn outcome(&self, stalemate_rounds: usize) -> Option<Outcome> {
+        let latest = self.latest()?;
+        let mut alive = latest
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0);
+        if let (Some((winner, _)), None) = (alive.next(), alive.next()) {
+            return Some(Outcome::Extinction(winner));
+        }
+
+        // Round 0 is the initial grid and never has conversions
+        let played = &self.rounds[1..];
+        if stalemate_rounds > 0
+            && played.len() >= stalemate_rounds
+            && played[played.len() - stalemate_rounds..]
+                .iter()
+                .all(|round| round.conversions == 0)
+        {
+            return Some(Outcome::Stalemate(stalemate_rounds));
+        }
+        None
+    }
+
+    pub fn describe_outcome(&self, outcome: Outcome) -> String {
+        let round = self.rounds.len().saturating_sub(1);
+        match outcome {
+            Outcome::Extinction(winner) => {
+                format!("Round {round}: only {} is left", self.types[winner])
+            }
+            Outcome::Stalemate(rounds) => {
+                format!("Round {round}: stalemate, no conversions for {rounds} rounds")
+            }
+        }
+    }
+
+    /// Share of every type still alive in the latest round, largest first
+    pub fn summary(&self) -> String {
+        let counts = match self.latest() {
+            Some(latest) => &latest.counts,
+            None => return String::new(),
+        };
+        let total = counts.iter().sum::<usize>().max(1);
+        let mut alive: Vec<_> = counts.iter().enumerate().filter(|(_, &c)| c > 0).collect();
+        alive.sort_by(|a, b| b.1.cmp(a.1));
+        alive
+            .into_iter()
+            .map(|(i, &count)| {
+                format!(
+                    "{}: {count} ({:.1}%)",
+                    self.types[i],
+                    count as f64 * 100.0 / total as f64
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// About `max_points` evenly spaced rounds, always including the latest one
+    pub fn sampled(&self, max_points: usize) -> impl Iterator<Item = &RoundStats> {
+        let stride = self.rounds.len().div_ceil(max_points.max(1)).max(1);
+        let last = self.rounds.len().saturating_sub(1);
+        self.rounds
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| i % stride == 0 || *i == last)
+            .map(|(_, round)| round)
+    }
+
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        write!(writer, "round,fights,conversions")?;
+        for name in &self.types {
+            write!(writer, ",{name}")?;
+        }
+        writeln!(writer)?;
+
+        for round in &self.rounds {
+            write!(
+                writer,
+                "{},{},{}",
+                round.round, round.fights, round.conversions
+            )?;
+            for count in &round.counts {
+                write!(writer, ",{count}")?;
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_json<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Writes JSON when the file has a `.json` extension and CSV otherwise
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if path.extension().is_some_and(|ext| ext == "json") {
+            self.write_json(&mut writer)?;
+        } else {
+            self.write_csv(&mut writer)?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statistics() -> Statistics {
+        let mut stats = Statistics::new(vec!["Rock".to_string(), "Paper".to_string()]);
+        stats.record(RoundStats {
+            round: 0,
+            fights: 0,
+            conversions: 0,
+            counts: vec![3, 1],
+        });
+        stats.record(RoundStats {
+            round: 1,
+            fights: 4,
+            conversions: 2,
+            counts: vec![1, 3],
+        });
+        stats
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut output = Vec::new();
+        statistics().write_csv(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "round,fights,conversions,Rock,Paper\n0,0,0,3,1\n1,4,2,1,3\n"
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut output = Vec::new();
+        statistics().write_json(&mut output).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(value["types"][1], "Paper");
+        assert_eq!(value["rounds"][1]["conversions"], 2);
+        assert_eq!(value["rounds"][1]["counts"][0], 1);
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut stats = statistics();
+        assert_eq!(stats.outcome(2), None);
+        assert_eq!(
+            stats.summary(),
+            "Paper: 3 (75.0%)\nRock: 1 (25.0%)".to_string()
+        );
+
+        stats.record(RoundStats {
+            round: 2,
+            fights: 4,
+            conversions: 0,
+            counts: vec![1, 3],
+        });
+        assert_eq!(stats.outcome(2), None);
+        stats.record(RoundStats {
+            round: 3,
+            fights: 4,
+            conversions: 0,
+            counts: vec![1, 3],
+        });
+        assert_eq!(stats.outcome(2), Some(Outcome::Stalemate(2)));
+        assert_eq!(stats.outcome(3), None);
+
+        stats.record(RoundStats {
+            round: 4,
+            fights: 4,
+            conversions: 1,
+            counts: vec![0, 4],
+        });
+        assert_eq!(stats.outcome(2), Some(Outcome::Extinction(1)));
+        assert_eq!(
+            stats.describe_outcome(Outcome::Extinction(1)),
+            "Round 4: only Paper is left"
+        );
+    }
+
+    #[test]
+    fn test_sampled() {
+        let mut stats = Statistics::new(vec!["Rock".to_string()]);
+        for round in 0..10 {
+            stats.record(RoundStats {
+                round,
+                ..RoundStats::default()
+            });
+        }
+        let rounds = |max_points| {
+            stats
+                .sampled(max_points)
+                .map(|round| round.round)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rounds(20), (0..10).collect::<Vec<_>>());
+        assert_eq!(rounds(4), vec![0, 3, 6, 9]);
+        assert_eq!(rounds(3), vec![0, 4, 8, 9]);
+    }
+}
@@ -4,13 +4,14 @@
 // And OCR does not handle them well.
 
 use crate::battle::Fighter;
-use crate::types::{Colored, GenerateRandomly};
+use crate::types::{Colored, GenerateRandomly, Typed};
 use lazy_static::lazy_static;
 use rand::distributions::{Distribution, Uniform};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use strum::{EnumCount, FromRepr};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount, FromRepr)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount, FromRepr, Deserialize, Serialize)]
 #[repr(usize)]
 pub enum StreetFighterType {
     Seth,
@@ -66,7 +67,7 @@ impl From<StreetFighterType> for usize {
     }
 }
 
-impl From<StreetFighterType> for nannou::image::Rgb<u8> {
+impl From<StreetFighterType> for [u8; 3] {
     fn from(kind: StreetFighterType) -> Self {
         match kind {
             StreetFighterType::Seth => [100, 122, 4],
@@ -109,7 +110,6 @@ impl From<StreetFighterType> for nannou::image::Rgb<u8> {
             StreetFighterType::THawk => [250, 254, 145],
             StreetFighterType::Dan => [136, 51, 0],
         }
-        .into()
     }
 }
 
@@ -160,12 +160,11 @@ fn get_effectiveness(attacker: StreetFighterType, defender: StreetFighterType) -
     EFFICIENCY[attacker as usize][defender as usize]
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct StreetFighter {
     health: i32,
     damage: i32,
     kind: StreetFighterType,
-    rng: rand::rngs::ThreadRng,
 }
 
 impl StreetFighter {
@@ -174,7 +173,6 @@ impl StreetFighter {
             health: 100,
             damage: 100,
             kind,
-            rng: rand::thread_rng(),
         }
     }
 
@@ -194,11 +192,13 @@ impl Fighter for StreetFighter {
         get_effectiveness(self.kind, defender.kind)
     }
 
-    fn fight(&self, defender: &mut Self) -> bool {
+    fn fight<R>(&self, defender: &mut Self, rng: &mut R) -> bool
+    where
+        R: Rng,
+    {
         // street fighter table is based on chance to win, not damage done
         // so we need to roll a die based on the chance
-        // we use defender's rng because they're &mut Self
-        let roll = defender.rng.gen_range(0..=100);
+        let roll = rng.gen_range(0..=100);
         let effectiveness = self.get_effectiveness(defender);
         if roll < effectiveness {
             defender.reset(self.kind);
@@ -224,11 +224,27 @@ impl GenerateRandomly for StreetFighter {
 }
 
 impl Colored for StreetFighter {
-    fn color(&self) -> nannou::image::Rgb<u8> {
+    fn color(&self) -> [u8; 3] {
         self.kind.into()
     }
 }
 
+impl Typed for StreetFighter {
+    fn type_index(&self) -> usize {
+        self.kind.into()
+    }
+
+    fn type_names() -> Vec<String> {
+        (0..StreetFighterType::COUNT)
+            .map(|kind| format!("{:?}", StreetFighterType::from(kind)))
+            .collect()
+    }
+
+    fn from_type_index(index: usize) -> Self {
+        Self::new(index.into())
+    }
+}
+
 impl core::fmt::Display for StreetFighter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(f, "{:?}", self.kind)
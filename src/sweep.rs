@@ -0,0 +1,309 @@
+// Runs every combination of a set of settings headless and summarizes who won each run.
+
+use crate::args::{validate_dimensions, Args, FighterType};
+use crate::battle::SelectionAlgorithm;
+use crate::simulation::create_simulation;
+use crate::stats::Outcome;
+use crate::types::FighterOptions;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// The settings to try, every field left out uses the value from the command line
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SweepSpec {
+    pub fighter_type: Option<FighterType>,
+    /// Grid sizes as `[width, height]`
+    pub sizes: Vec<(usize, usize)>,
+    pub selection_algorithms: Vec<SelectionAlgorithm>,
+    pub fightown: Vec<bool>,
+    pub dual_types: Vec<bool>,
+    pub base_stats: Vec<bool>,
+    pub seeds: Vec<u64>,
+    /// Maximum number of rounds per run, runs end earlier on extinction or a stalemate
+    pub rounds: Vec<usize>,
+    /// CSV file the results are written to, sweep.csv by default
+    pub output: Option<PathBuf>,
+}
+
+impl SweepSpec {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(io::BufReader::new(file))?)
+    }
+
+    /// Settings of every run, `base` provides everything the spec does not vary
+    pub fn runs(&self, base: &Args) -> io::Result<Vec<Args>> {
+        let fighter_type = self
+            .fighter_type
+            .clone()
+            .unwrap_or_else(|| base.fighter_type.clone());
+        let sizes = or_base(&self.sizes, (base.width, base.height));
+        let selection_algorithms = or_base(&self.selection_algorithms, base.selection_algorithm());
+        let fightown = or_base(&self.fightown, base.fightown);
+        let base_stats = or_base(&self.base_stats, base.base_stats);
+        let options: Vec<_> = or_base(&self.dual_types, base.dual_types)
+            .into_iter()
+            .flat_map(|dual_types| {
+                base_stats.iter().map(move |&base_stats| FighterOptions {
+                    dual_types,
+                    base_stats,
+                })
+            })
+            .collect();
+        let seeds = match self.seeds.is_empty() {
+            true => vec![base.seed],
+            false => self.seeds.iter().copied().map(Some).collect(),
+        };
+        let rounds = or_base(&self.rounds, base.rounds);
+
+        for &(width, height) in &sizes {
+            validate_dimensions(width, height)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        }
+
+        let mut runs = Vec::new();
+        for &(width, height) in &sizes {
+            for &selection_algorithm in &selection_algorithms {
+                for &fightown in &fightown {
+                    for options in &options {
+                        for &rounds in &rounds {
+                            for &seed in &seeds {
+                                let mut args = base.clone();
+                                args.fighter_type = fighter_type.clone();
+                                args.width = width;
+                                args.height = height;
+                                args.random =
+                                    selection_algorithm == SelectionAlgorithm::RandomNeighbour;
+                                args.fightown = fightown;
+                                args.dual_types = options.dual_types;
+                                args.base_stats = options.base_stats;
+                                args.rounds = rounds;
+                                args.seed = seed;
+                                args.load_snapshot = None;
+                                runs.push(args);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(runs)
+    }
+}
+
+fn or_base<T: Clone>(values: &[T], base: T) -> Vec<T> {
+    if values.is_empty() {
+        vec![base]
+    } else {
+        values.to_vec()
+    }
+}
+
+#[derive(Debug)]
+pub struct SweepResult {
+    pub args: Args,
+    pub outcome: Option<Outcome>,
+    /// Rounds played until the battle ended or the round limit was reached
+    pub rounds: usize,
+    /// Type with the most fighters at the end
+    pub winner: usize,
+    pub types: Vec<String>,
+    /// Final share of every type, between 0 and 1, indexed like `types`
+    pub shares: Vec<f64>,
+}
+
+/// Plays a single run until only one type is left, it is a stalemate or the round limit is hit
+pub fn run_once(args: &Args) -> io::Result<SweepResult> {
+    let mut simulation = create_simulation(args, &args.fighter_type)?;
+    let mut outcome = None;
+    while outcome.is_none() && simulation.round() < args.rounds {
+        simulation.action();
+        outcome = simulation.outcome(args.stalemate_rounds);
+    }
+
+    let counts = &simulation.stats().latest().unwrap().counts;
+    let total = counts.iter().sum::<usize>().max(1);
+    let winner = match outcome {
+        Some(Outcome::Extinction(winner)) => winner,
+        // The first type with the most fighters
+        _ => (0..counts.len())
+            .rev()
+            .max_by_key(|&i| counts[i])
+            .unwrap_or(0),
+    };
+
+    Ok(SweepResult {
+        args: args.clone(),
+        outcome,
+        rounds: simulation.round(),
+        winner,
+        types: simulation.stats().types().to_vec(),
+        shares: counts
+            .iter()
+            .map(|&count| count as f64 / total as f64)
+            .collect(),
+    })
+}
+
+/// Runs every combination of `spec` on all cores, results are in the same order as `spec.runs`
+pub fn run_sweep(spec: &SweepSpec, base: &Args) -> io::Result<Vec<SweepResult>> {
+    spec.runs(base)?.par_iter().map(run_once).collect()
+}
+
+pub fn write_csv<W>(results: &[SweepResult], mut writer: W) -> io::Result<()>
+where
+    W: Write,
+{
+    write!(
+        writer,
+        "width,height,selection,fightown,dual_types,base_stats,seed,max_rounds,outcome,rounds,winner"
+    )?;
+    let types = results.first().map_or(&[][..], |result| &result.types);
+    for name in types {
+        write!(writer, ",{name}")?;
+    }
+    writeln!(writer)?;
+
+    for result in results {
+        let args = &result.args;
+        let outcome = match result.outcome {
+            Some(Outcome::Extinction(_)) => "extinction",
+            Some(Outcome::Stalemate(_)) => "stalemate",
+            None => "round-limit",
+        };
+        write!(
+            writer,
+            "{},{},{:?},{},{},{},{},{},{outcome},{},{}",
+            args.width,
+            args.height,
+            args.selection_algorithm(),
+            args.fightown,
+            args.dual_types,
+            args.base_stats,
+            args.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+            args.rounds,
+            result.rounds,
+            result.types[result.winner]
+        )?;
+        for share in &result.shares {
+            write!(writer, ",{share:.4}")?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Runs the sweep described by the JSON file at `path`, writes the results as CSV and prints how
+/// often every type won
+pub fn run_sweep_file(path: &Path, base: &Args) -> io::Result<()> {
+    let spec = SweepSpec::open(path)?;
+    let results = run_sweep(&spec, base)?;
+
+    let output = spec
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("sweep.csv"));
+    let mut writer = BufWriter::new(File::create(&output)?);
+    write_csv(&results, &mut writer)?;
+    writer.flush()?;
+
+    // Number of runs and rounds to extinction of every winner
+    let mut wins = BTreeMap::new();
+    for result in &results {
+        let (runs, extinctions) = wins.entry(result.winner).or_insert((0, Vec::new()));
+        *runs += 1;
+        if let Some(Outcome::Extinction(_)) = result.outcome {
+            extinctions.push(result.rounds);
+        }
+    }
+    let mut wins: Vec<_> = wins.into_iter().collect();
+    wins.sort_by_key(|(_, (runs, _))| Reverse(*runs));
+    for (winner, (runs, extinctions)) in wins {
+        print!(
+            "{}: won {runs} of {} runs",
+            results[0].types[winner],
+            results.len()
+        );
+        if !extinctions.is_empty() {
+            let average = extinctions.iter().sum::<usize>() / extinctions.len();
+            print!(
+                ", {} by extinction after {average} rounds on average",
+                extinctions.len()
+            );
+        }
+        println!();
+    }
+    println!("Results written to {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_runs() {
+        let base = Args::parse_from(["test", "-x", "64", "-n", "50"]);
+        let spec: SweepSpec = serde_json::from_str(
+            r#"{
+                "fighter_type": "rock-paper-scissors",
+                "sizes": [[32, 32], [48, 32]],
+                "selection_algorithms": ["WeakestNeighbour", "RandomNeighbour"],
+                "seeds": [1, 2, 3]
+            }"#,
+        )
+        .unwrap();
+        let runs = spec.runs(&base).unwrap();
+        assert_eq!(runs.len(), 12);
+        assert!(runs
+            .iter()
+            .all(|args| args.fighter_type == FighterType::RockPaperScissors && args.rounds == 50));
+        assert_eq!(runs[11].width, 48);
+        assert!(runs[11].random);
+        assert_eq!(runs[11].seed, Some(3));
+
+        let too_small: SweepSpec = serde_json::from_str(r#"{ "sizes": [[16, 32]] }"#).unwrap();
+        assert!(too_small.runs(&base).is_err());
+
+        // Settings from a file are checked before a run starts instead of panicking during it
+        let mut args = runs[0].clone();
+        args.reproduction = Some(1.5);
+        assert!(run_once(&args).is_err());
+    }
+
+    #[test]
+    fn test_run_sweep() {
+        let base = Args::parse_from(["test", "-t", "rock-paper-scissors", "-x", "32", "-y", "32"]);
+        let spec = SweepSpec {
+            seeds: vec![4, 5],
+            rounds: vec![20],
+            ..SweepSpec::default()
+        };
+        let results = run_sweep(&spec, &base).unwrap();
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            assert!(result.rounds <= 20);
+            assert!((result.shares.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        // Runs are reproducible no matter which thread played them
+        assert_eq!(
+            run_once(&results[1].args).unwrap().shares,
+            results[1].shares
+        );
+
+        let mut output = Vec::new();
+        write_csv(&results, &mut output).unwrap();
+        let csv = String::from_utf8(output).unwrap();
+        assert!(csv.starts_with(
+            "width,height,selection,fightown,dual_types,base_stats,seed,max_rounds,outcome,rounds,winner,Rock,Paper,Scissor\n"
+        ));
+        assert_eq!(csv.lines().count(), 3);
+    }
+}
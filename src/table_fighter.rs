@@ -0,0 +1,446 @@
+// Fighters whose types, colors and matchups are read from a JSON file instead of being compiled
+// in. See the files in the `tables` directory for the expected format.
+
+use crate::battle::Fighter;
+use crate::types::{Colored, GenerateRandomly, Typed};
+use crate::vitality::Vitality;
+use once_cell::sync::OnceCell;
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// Needed because GenerateRandomly has no way to pass the table along
+static TABLE: OnceCell<FighterTable> = OnceCell::new();
+
+/// How the effectiveness values of a table are used to resolve a fight
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Resolution {
+    /// Effectiveness is a percentage of the attacker's damage, like the Pokémon table
+    #[default]
+    Damage,
+    /// Effectiveness is the chance in percent to win the fight, like the Street Fighter table
+    Chance,
+}
+
+/// Health, attack, defense and speed of a type, Pokémon use them with base stats enabled
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub struct BaseStats {
+    pub health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TableType {
+    pub name: String,
+    pub color: [u8; 3],
+    #[serde(default)]
+    pub stats: Option<BaseStats>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FighterTable {
+    pub health: i32,
+    pub damage: i32,
+    #[serde(default)]
+    pub resolution: Resolution,
+    pub types: Vec<TableType>,
+    pub effectiveness: Vec<Vec<i32>>,
+}
+
+impl FighterTable {
+    pub fn from_reader<Rd>(reader: Rd) -> io::Result<Self>
+    where
+        Rd: io::Read,
+    {
+        let table: Self = serde_json::from_reader(reader)?;
+        table.validate()?;
+        Ok(table)
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_reader(io::BufReader::new(file))
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let count = self.types.len();
+        if count == 0 {
+            return Err(invalid_data("fighter table has no types".to_string()));
+        }
+
+        if self.effectiveness.len() != count
+            || self.effectiveness.iter().any(|row| row.len() != count)
+        {
+            return Err(invalid_data(format!(
+                "effectiveness should be a {count}x{count} matrix"
+            )));
+        }
+
+        let with_stats = self
+            .types
+            .iter()
+            .filter(|kind| kind.stats.is_some())
+            .count();
+        if with_stats != 0 && with_stats != count {
+            return Err(invalid_data(
+                "stats should be given for every type or none".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn get_effectiveness(&self, attacker: usize, defender: usize) -> i32 {
+        self.effectiveness[attacker][defender]
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TableFighter {
+    vitality: Vitality,
+    damage: i32,
+    kind: usize,
+    #[serde(skip, default = "loaded_table")]
+    table: &'static FighterTable,
+}
+
+fn loaded_table() -> &'static FighterTable {
+    TABLE.get().expect("fighter table should be loaded")
+}
+
+impl TableFighter {
+    /// Loads the table used by `generate_randomly`. Can only be done once per process.
+    pub fn load_table(path: &Path) -> io::Result<()> {
+        let table = FighterTable::open(path)?;
+        TABLE
+            .set(table)
+            .map_err(|_| io::Error::other("fighter table already loaded"))
+    }
+
+    pub fn is_table_loaded() -> bool {
+        TABLE.get().is_some()
+    }
+
+    pub fn new(table: &'static FighterTable, kind: usize) -> Self {
+        TableFighter {
+            vitality: Vitality::new(table.health),
+            damage: table.damage,
+            kind,
+            table,
+        }
+    }
+
+    fn reset(&mut self, kind: usize) {
+        self.vitality = Vitality::new(self.table.health);
+        self.damage = self.table.damage;
+        self.kind = kind;
+    }
+}
+
+impl Fighter for TableFighter {
+    fn should_fight(&self, defender: &Self) -> bool {
+        self.kind != defender.kind
+    }
+
+    fn get_effectiveness(&self, defender: &Self) -> i32 {
+        self.table.get_effectiveness(self.kind, defender.kind)
+    }
+
+    fn fight<R>(&self, defender: &mut Self, rng: &mut R) -> bool
+    where
+        R: Rng,
+    {
+        let effectiveness = self.get_effectiveness(defender);
+        let is_dead = match self.table.resolution {
+            Resolution::Damage => {
+                let damage = self.vitality.scale(self.damage) * effectiveness / 100;
+                defender.vitality.take_damage(damage)
+            }
+            Resolution::Chance => rng.gen_range(0..=100) < effectiveness,
+        };
+
+        if is_dead {
+            defender.reset(self.kind);
+        }
+        is_dead
+    }
+
+    fn health(&self) -> f32 {
+        self.vitality.share(self.table.health)
+    }
+
+    fn regenerate(&mut self, rate: f64) {
+        self.vitality.regenerate(self.table.health, rate);
+    }
+
+    fn level(&self) -> u32 {
+        self.vitality.level()
+    }
+
+    fn gain_experience(&mut self) {
+        self.vitality.gain_experience(self.table.health);
+    }
+
+    fn inherit_experience(&mut self, winner: &Self) {
+        self.vitality
+            .inherit_experience(&winner.vitality, self.table.health);
+    }
+}
+
+impl GenerateRandomly for TableFighter {
+    fn generate_randomly<R>(rng: &mut R) -> Self
+    where
+        R: Rng,
+    {
+        let table = loaded_table();
+        let t = Uniform::new(0, table.types.len()).sample(rng);
+        Self::new(table, t)
+    }
+}
+
+impl Colored for TableFighter {
+    fn color(&self) -> [u8; 3] {
+        self.table.types[self.kind].color
+    }
+}
+
+impl Typed for TableFighter {
+    fn type_index(&self) -> usize {
+        self.kind
+    }
+
+    fn type_names() -> Vec<String> {
+        loaded_table()
+            .types
+            .iter()
+            .map(|kind| kind.name.clone())
+            .collect()
+    }
+
+    fn from_type_index(index: usize) -> Self {
+        Self::new(loaded_table(), index)
+    }
+}
+
+impl core::fmt::Display for TableFighter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}{}", self.table.types[self.kind].name, self.vitality)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::FighterType;
+    use crate::battle::{Battle, SelectionAlgorithm};
+    use crate::pokemon::{Pokemon, PokemonType};
+    use crate::render::{render, RenderMode};
+    use crate::rps::{RPSType, RPS};
+    use crate::snapshot::{load_snapshot, save_snapshot};
+    use crate::street_fighter::{StreetFighter, StreetFighterType};
+    use image::RgbImage;
+    use lazy_static::lazy_static;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use strum::EnumCount;
+
+    lazy_static! {
+        static ref POKEMON: FighterTable =
+            FighterTable::from_reader(include_str!("../tables/pokemon.json").as_bytes()).unwrap();
+        static ref RPS_TABLE: FighterTable =
+            FighterTable::from_reader(include_str!("../tables/rps.json").as_bytes()).unwrap();
+        static ref STREET_FIGHTER: FighterTable =
+            FighterTable::from_reader(include_str!("../tables/street_fighter.json").as_bytes())
+                .unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let no_types = r#"{ "health": 1, "damage": 1, "types": [], "effectiveness": [] }"#;
+        assert!(FighterTable::from_reader(no_types.as_bytes()).is_err());
+
+        let wrong_size = r#"{
+            "health": 1,
+            "damage": 1,
+            "types": [{ "name": "A", "color": [0, 0, 0] }],
+            "effectiveness": [[0, 0]]
+        }"#;
+        assert!(FighterTable::from_reader(wrong_size.as_bytes()).is_err());
+
+        let some_stats = r#"{
+            "health": 1,
+            "damage": 1,
+            "types": [
+                { "name": "A", "color": [0, 0, 0] },
+                {
+                    "name": "B",
+                    "color": [0, 0, 0],
+                    "stats": { "health": 1, "attack": 1, "defense": 1, "speed": 1 }
+                }
+            ],
+            "effectiveness": [[0, 0], [0, 0]]
+        }"#;
+        assert!(FighterTable::from_reader(some_stats.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_pokemon_parity() {
+        assert_eq!(POKEMON.types.len(), PokemonType::COUNT);
+        for attacker in 0..PokemonType::COUNT {
+            let pokemon = Pokemon::new(attacker.into());
+            let fighter = TableFighter::new(&POKEMON, attacker);
+            assert_eq!(format!("{pokemon}"), format!("{fighter}"));
+            assert_eq!(pokemon.color(), fighter.color());
+
+            for defender in 0..PokemonType::COUNT {
+                assert_eq!(
+                    pokemon.get_effectiveness(&Pokemon::new(defender.into())),
+                    fighter.get_effectiveness(&TableFighter::new(&POKEMON, defender))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rps_parity() {
+        assert_eq!(RPS_TABLE.types.len(), RPSType::COUNT);
+        for attacker in 0..RPSType::COUNT {
+            let rps = RPS::new(attacker.into());
+            let fighter = TableFighter::new(&RPS_TABLE, attacker);
+            assert_eq!(format!("{rps}"), format!("{fighter}"));
+            assert_eq!(rps.color(), fighter.color());
+
+            for defender in 0..RPSType::COUNT {
+                assert_eq!(
+                    rps.get_effectiveness(&RPS::new(defender.into())),
+                    fighter.get_effectiveness(&TableFighter::new(&RPS_TABLE, defender))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_street_fighter_parity() {
+        assert_eq!(STREET_FIGHTER.types.len(), StreetFighterType::COUNT);
+        for attacker in 0..StreetFighterType::COUNT {
+            let street_fighter = StreetFighter::new(attacker.into());
+            let fighter = TableFighter::new(&STREET_FIGHTER, attacker);
+            assert_eq!(format!("{street_fighter}"), format!("{fighter}"));
+            assert_eq!(street_fighter.color(), fighter.color());
+
+            for defender in 0..StreetFighterType::COUNT {
+                assert_eq!(
+                    street_fighter.get_effectiveness(&StreetFighter::new(defender.into())),
+                    fighter.get_effectiveness(&TableFighter::new(&STREET_FIGHTER, defender))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_damage() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let normal = TableFighter::new(&POKEMON, PokemonType::Normal as usize);
+        let mut fire = TableFighter::new(&POKEMON, PokemonType::Fire as usize);
+
+        assert!(!normal.fight(&mut fire, &mut rng));
+        assert_eq!(fire.kind, PokemonType::Fire as usize);
+        assert!(normal.fight(&mut fire, &mut rng));
+        assert_eq!(fire.kind, PokemonType::Normal as usize);
+        assert_eq!(fire.vitality.health(), POKEMON.health);
+    }
+
+    #[test]
+    fn test_snapshot_checks_table() {
+        TABLE.get_or_init(|| {
+            FighterTable::from_reader(include_str!("../tables/pokemon.json").as_bytes()).unwrap()
+        });
+        let path = std::env::temp_dir().join(format!(
+            "poke-fighting-rust-table-snapshot-test-{}.json",
+            std::process::id()
+        ));
+        let rng = ChaCha8Rng::seed_from_u64(5);
+        let battle =
+            Battle::<TableFighter>::new(32, 32, SelectionAlgorithm::WeakestNeighbour, true, rng);
+        save_snapshot(&battle, &FighterType::Custom, &path).unwrap();
+        let saved: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+
+        // Snapshots saved with another table, or changed by hand, fail to load
+        let load = |change: &dyn Fn(&mut serde_json::Value)| {
+            let mut snapshot = saved.clone();
+            change(&mut snapshot);
+            serde_json::to_writer(File::create(&path).unwrap(), &snapshot).unwrap();
+            load_snapshot::<TableFighter, ChaCha8Rng>(&path, &FighterType::Custom)
+                .map_err(|err| err.kind())
+        };
+        assert!(load(&|_| {}).is_ok());
+        assert_eq!(
+            load(&|snapshot| snapshot["types"] = serde_json::json!(["Rock", "Paper", "Scissor"]))
+                .err(),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            load(&|snapshot| snapshot["battle"]["fighters"]["data"][0]["kind"] = 18.into()).err(),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            load(&|snapshot| {
+                snapshot["battle"]["fighters"]["data"]
+                    .as_array_mut()
+                    .unwrap()
+                    .pop();
+            })
+            .err(),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            load(&|snapshot| snapshot["battle"]["fighters"]["blocked"] = vec![true; 3].into())
+                .err(),
+            Some(io::ErrorKind::InvalidData)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_battle_parity() {
+        TABLE.get_or_init(|| {
+            FighterTable::from_reader(include_str!("../tables/pokemon.json").as_bytes()).unwrap()
+        });
+
+        let run = |image: &mut RgbImage, table: bool| {
+            let rng = ChaCha8Rng::seed_from_u64(3);
+            if table {
+                let mut battle = Battle::<TableFighter>::new(
+                    64,
+                    64,
+                    SelectionAlgorithm::WeakestNeighbour,
+                    true,
+                    rng,
+                );
+                (0..10).for_each(|_| battle.action());
+                render(&battle, image, RenderMode::Type);
+            } else {
+                let mut battle =
+                    Battle::<Pokemon>::new(64, 64, SelectionAlgorithm::WeakestNeighbour, true, rng);
+                (0..10).for_each(|_| battle.action());
+                render(&battle, image, RenderMode::Type);
+            }
+        };
+
+        let mut expected = RgbImage::new(64, 64);
+        let mut actual = RgbImage::new(64, 64);
+        run(&mut expected, false);
+        run(&mut actual, true);
+        assert_eq!(expected, actual);
+    }
+}
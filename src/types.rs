@@ -1,4 +1,16 @@
+use crate::grid::{Grid2D, Size};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Settings of a battle that change the fighters themselves, only Pokémon have any so far
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct FighterOptions {
+    /// Half of the fighters get a second type
+    pub dual_types: bool,
+    /// Health, attack, defense and speed differ by type
+    pub base_stats: bool,
+}
 
 pub trait GenerateRandomly {
     fn generate_randomly<R>(rng: &mut R) -> Self
@@ -6,6 +18,35 @@ pub trait GenerateRandomly {
         R: Rng;
 }
 
+/// Decides which fighters a new grid starts with
+pub trait Initializer<T> {
+    fn populate<R>(&self, size: Size, rng: &mut R) -> Grid2D<T>
+    where
+        R: Rng;
+}
+
 pub trait Colored {
-    fn color(&self) -> nannou::image::Rgb<u8>;
+    fn color(&self) -> [u8; 3];
+}
+
+pub trait Typed {
+    /// Index of the fighter's type into `type_names`
+    fn type_index(&self) -> usize;
+    fn type_names() -> Vec<String>;
+    /// A fresh fighter of the given type
+    fn from_type_index(index: usize) -> Self;
+    /// A fresh fighter of the same kind, for a fighter that reproduces
+    fn offspring(&self) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_type_index(self.type_index())
+    }
+    /// The fighter for a pixel of an image, `closest_type` is the type with the closest color
+    fn from_color(_color: [u8; 3], closest_type: usize) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_type_index(closest_type)
+    }
 }
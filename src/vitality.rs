@@ -0,0 +1,132 @@
+use crate::experience::Experience;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Health and experience of a fighter. `base` is the full health of the fighter at level 1,
+/// which every fighter type works out in its own way.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Vitality {
+    health: i32,
+    /// Regenerated health below a whole point
+    #[serde(default)]
+    partial_health: f32,
+    #[serde(default)]
+    experience: Experience,
+}
+
+impl Vitality {
+    /// Full health at level 1
+    pub fn new(base: i32) -> Self {
+        Vitality {
+            health: base,
+            partial_health: 0.0,
+            experience: Experience::default(),
+        }
+    }
+
+    pub fn health(&self) -> i32 {
+        self.health
+    }
+
+    pub fn full_health(&self, base: i32) -> i32 {
+        self.experience.scale(base)
+    }
+
+    /// Remaining share of the full health, between 0 and 1
+    pub fn share(&self, base: i32) -> f32 {
+        self.health as f32 / self.full_health(base) as f32
+    }
+
+    /// Returns true when no health is left
+    pub fn take_damage(&mut self, damage: i32) -> bool {
+        self.health -= damage;
+
+        self.health <= 0
+    }
+
+    /// Scales a damage value at level 1 to the current level
+    pub fn scale(&self, value: i32) -> i32 {
+        self.experience.scale(value)
+    }
+
+    pub fn level(&self) -> u32 {
+        self.experience.level()
+    }
+
+    /// Heals `rate` of the full health. Whatever is below a whole point is kept, so small rates
+    /// still heal over several rounds.
+    pub fn regenerate(&mut self, base: i32, rate: f64) {
+        let full = self.full_health(base);
+        let gained = full as f64 * rate + self.partial_health as f64;
+        self.health = (self.health + gained as i32).min(full);
+        self.partial_health = if self.health < full {
+            gained.fract() as f32
+        } else {
+            0.0
+        };
+    }
+
+    /// A level up adds the health it gains on top of the current health
+    pub fn gain_experience(&mut self, base: i32) {
+        let full = self.full_health(base);
+        self.experience.gain();
+        self.health += self.full_health(base) - full;
+    }
+
+    /// Takes over some of the experience of `winner` and starts at full health
+    pub fn inherit_experience(&mut self, winner: &Vitality, base: i32) {
+        self.experience = Experience::inherited(&winner.experience);
+        self.health = self.full_health(base);
+    }
+
+    /// Keeps the share of health left when the full health at level 1 changes from `old` to
+    /// `new`, so the change does not heal or hurt
+    pub fn rebase(&mut self, old: i32, new: i32) {
+        let share = self.share(old);
+        self.health = (share * self.full_health(new) as f32).round() as i32;
+    }
+}
+
+/// Nothing at level 1, otherwise the level to add after the name of a fighter
+impl fmt::Display for Vitality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level() {
+            1 => Ok(()),
+            level => write!(f, ", level {level}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vitality() {
+        let mut vitality = Vitality::new(80);
+        assert!(!vitality.take_damage(60));
+        assert_eq!(vitality.share(80), 0.25);
+        vitality.regenerate(80, 0.25);
+        assert_eq!(vitality.share(80), 0.5);
+
+        // 0.5% of 80 is less than a point per round, it adds up to 2 points over 6 rounds
+        (0..6).for_each(|_| vitality.regenerate(80, 0.005));
+        assert_eq!(vitality.health(), 42);
+        vitality.regenerate(80, 1.0);
+        assert_eq!(vitality.health(), 80);
+        assert_eq!(vitality.to_string(), "");
+
+        (0..6).for_each(|_| vitality.gain_experience(80));
+        assert_eq!(vitality.health(), 96);
+        assert_eq!(vitality.scale(40), 48);
+        assert_eq!(vitality.to_string(), ", level 3");
+
+        let mut loser = Vitality::new(80);
+        loser.inherit_experience(&vitality, 80);
+        assert_eq!((loser.level(), loser.health()), (2, 88));
+
+        loser.take_damage(44);
+        loser.rebase(80, 60);
+        assert_eq!((loser.share(60), loser.health()), (0.5, 33));
+    }
+}